@@ -0,0 +1,55 @@
+//! Decode throughput benchmarks, for catching performance regressions in the wrapper layer.
+//!
+//! Run with `cargo bench --features bench,std` once `gs-16b-2c-44100hz.mp3` is in the crate root.
+//! `decode` reports frames/sec and samples/sec for the whole file, `find_sync_word` measures the
+//! worst case of searching data that has no sync word in it.
+//!
+//! This decodes the way that gets the most out of EasyMode: a stream buffer large enough that it
+//! rarely needs compacting, data added in large chunks and an output buffer that fits any frame.
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use threepm::easy_mode::EasyModeN;
+use threepm::mp3::{Mp3, MAX_OUTPUT_SAMPLES};
+
+static MP3: &[u8] = include_bytes!("../gs-16b-2c-44100hz.mp3");
+
+/// Size of the chunks MP3 data is added to the stream buffer in
+const CHUNK_SZ: usize = 2048;
+
+/// Decode all of `mp3`, returning the number of frames and samples decoded
+fn decode_stream(mp3: &[u8]) -> (u64, u64) {
+    let mut easy = EasyModeN::<8192>::new();
+    let mut buf = [0i16; MAX_OUTPUT_SAMPLES];
+    let mut frames = easy.frames(&mut buf).source(mp3.chunks(CHUNK_SZ));
+    let (mut frame_count, mut sample_count) = (0, 0);
+    while let Some(Ok(samples)) = frames.next() {
+        black_box(frames.output());
+        frame_count += 1;
+        sample_count += samples as u64;
+    }
+    (frame_count, sample_count)
+}
+
+fn decode(c: &mut Criterion) {
+    let (frames, samples) = decode_stream(MP3);
+    assert!(frames > 0, "sample MP3 didn't decode");
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(frames));
+    group.bench_function("frames", |b| b.iter(|| decode_stream(black_box(MP3))));
+    group.throughput(Throughput::Elements(samples));
+    group.bench_function("samples", |b| b.iter(|| decode_stream(black_box(MP3))));
+    group.finish();
+}
+
+fn find_sync_word(c: &mut Criterion) {
+    // 0xFF on its own looks like the start of a sync word, so every byte has to be checked twice
+    let junk: Vec<u8> = (0..64 * 1024).map(|i| if i % 2 == 0 { 0xFF } else { 0x00 }).collect();
+    let mut group = c.benchmark_group("find_sync_word");
+    group.throughput(Throughput::Bytes(junk.len() as u64));
+    group.bench_function("junk", |b| {
+        b.iter(|| assert_eq!(Mp3::find_sync_word_offset(black_box(&junk)), None))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode, find_sync_word);
+criterion_main!(benches);
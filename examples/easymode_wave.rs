@@ -44,17 +44,23 @@ fn main() {
     let syncd = easy.skip_to_next_sync_word();
     println!("Synced: {syncd}");
 
-    let frame = easy.mp3_info().expect("Could not find MP3 frame in buffer");
+    // Keep adding data until there's a frame header to read the metadata from
+    let frame = loop {
+        match easy.mp3_info() {
+            Ok(frame) => break frame,
+            Err(EasyModeErr::NotReady) => {
+                let mp3data = mp3_loader.next().expect("Out of data!");
+                easy.add_data(mp3data);
+                easy.skip_to_next_sync_word();
+            }
+            Err(e) => panic!("Invalid MP3 frame header: {e}"),
+        }
+    };
     println!("First MP3 frame info: {:?}", frame);
     let mut buf = [0i16; BUFF_LEN];
 
     // Set our Wave metadata based on mp3 audio format
-    let spec = hound::WavSpec {
-        channels: frame.nChans as u16,
-        sample_rate: frame.samprate as u32,
-        bits_per_sample: frame.bitsPerSample as u16,
-        sample_format: hound::SampleFormat::Int,
-    };
+    let spec = frame.to_wav_spec();
 
     // Set up our wave file writer
     let path: &Path = "audio.wav".as_ref();
@@ -38,12 +38,7 @@ fn main() {
     println!("info: {:?}", frame);
 
     // Set our Wave metadata based on mp3 audio format
-    let spec = hound::WavSpec {
-        channels: frame.nChans as u16,
-        sample_rate: frame.samprate as u32,
-        bits_per_sample: frame.bitsPerSample as u16,
-        sample_format: hound::SampleFormat::Int,
-    };
+    let spec = frame.to_wav_spec();
 
     let path: &Path = "audio.wav".as_ref();
 
@@ -2,4 +2,5 @@
 
 bindgen ffi/bindgen.h \
         --use-core --ctypes-prefix core::ffi \
+        --with-derive-custom-struct '_MP3FrameInfo=PartialEq,Eq,Hash' \
         --output src/ffi.rs -- -Iffi/ThreePM/src
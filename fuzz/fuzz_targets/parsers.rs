@@ -0,0 +1,54 @@
+//! Feed arbitrary bytes to the stream parsers, which must never panic or read out of bounds.
+//!
+//! Run with `cargo +nightly fuzz run parsers` from the root of this project.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use threepm::easy_mode::EasyMode;
+use threepm::mp3::Mp3;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(offset) = Mp3::find_sync_word_offset(data) {
+        assert!(offset < data.len());
+    }
+    if let Some((offset, id3)) = Mp3::find_id3v2(data) {
+        assert!(offset + 10 <= data.len());
+        let _ = id3.tag_len();
+    }
+    if let Some((offset, ape)) = Mp3::find_apev2(data) {
+        assert!(offset <= data.len());
+        let _ = ape.size;
+    }
+    let _ = Mp3::find_id3v1(data);
+    let _ = Mp3::estimate_duration_secs(data);
+    for (offset, header) in data.windows(4).enumerate() {
+        let frame = &data[offset..];
+        if Mp3::is_valid_frame_header(header.try_into().unwrap()) {
+            assert!(Mp3::parse_frame_info(frame).is_some());
+        }
+        let _ = Mp3::verify_frame_crc(frame);
+        let _ = Mp3::check_conformance(frame);
+        let _ = Mp3::parse_vbr_header(frame);
+        let _ = Mp3::parse_replaygain(frame);
+    }
+
+    // The first byte picks the chunk size, so that tags and frames get split across add_data calls
+    let Some((chunk_size, stream)) = data.split_first() else {
+        return;
+    };
+    let mut easy = EasyMode::new();
+    for chunk in stream.chunks(*chunk_size as usize + 1) {
+        let mut chunk = chunk;
+        while !chunk.is_empty() {
+            let added = easy.add_data(chunk);
+            chunk = &chunk[added..];
+            let used = easy.buffer_used();
+            let _ = easy.mp3_decode_ready();
+            assert!(easy.buffer_used() <= used);
+            if added == 0 && easy.buffer_used() == used {
+                // the buffer is full of data that can't be skipped without decoding it
+                return;
+            }
+        }
+    }
+});
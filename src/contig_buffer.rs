@@ -1,15 +1,40 @@
 use core::{fmt, slice::Chunks};
 
+#[cfg(test)]
 const BUFF_SZ: usize = 1024;
 const CHUNK_SZ: usize = 512;
+/// The bytes backing a [Buffer], either stored inline or provided by the caller
+#[derive(Debug)]
+pub(crate) enum Storage<const N: usize> {
+    Inline([u8; N]),
+    Borrowed(&'static mut [u8]),
+}
+
+impl<const N: usize> Storage<N> {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Storage::Inline(bytes) => bytes,
+            Storage::Borrowed(bytes) => bytes,
+        }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        match self {
+            Storage::Inline(bytes) => bytes,
+            Storage::Borrowed(bytes) => bytes,
+        }
+    }
+}
+
 #[derive(Debug)]
-pub(crate) struct Buffer {
-    pub mp3_byte_buffer: [u8; BUFF_SZ],
+pub(crate) struct Buffer<const N: usize> {
+    pub mp3_byte_buffer: Storage<N>,
     pub buff_start: usize,
     pub buff_end: usize,
+    pub compact_ratio: u8,
 }
 
-impl fmt::Display for Buffer {
+impl<const N: usize> fmt::Display for Buffer<N> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
@@ -23,35 +48,89 @@ impl fmt::Display for Buffer {
 }
 
 #[allow(unused)]
-impl Buffer {
+impl<const N: usize> Buffer<N> {
     pub const fn new() -> Self {
         Self {
-            mp3_byte_buffer: [0u8; BUFF_SZ],
+            mp3_byte_buffer: Storage::Inline([0u8; N]),
             buff_start: 0,
             buff_end: 0,
+            compact_ratio: 0,
         }
     }
 
+    /// Use `storage` for the buffer instead of the inline array, so the buffer is `storage.len()` bytes
+    pub const fn with_storage(storage: &'static mut [u8]) -> Self {
+        Self {
+            mp3_byte_buffer: Storage::Borrowed(storage),
+            buff_start: 0,
+            buff_end: 0,
+            compact_ratio: 0,
+        }
+    }
+
+    /// The size of the buffer
+    pub fn capacity(&self) -> usize {
+        self.mp3_byte_buffer.as_slice().len()
+    }
+
+    /// Only shuffle data to the start of the buffer once `percent` of the buffer has been consumed from the start,
+    /// or when there is no space left at the end. 0 shuffles whenever the space at the end is too small
+    pub fn set_compact_ratio(&mut self, percent: u8) {
+        self.compact_ratio = percent.min(100);
+    }
+
+    /// How much of the buffer is used up to the end of the data, including space at the start that has been
+    /// consumed but not reclaimed by shuffling yet
+    pub fn used_contiguous(&self) -> usize {
+        self.buff_end
+    }
+
+    /// Whether we should shuffle data to the start of the buffer to make room for `len` bytes
+    fn should_compact(&self, len: usize) -> bool {
+        let ratio = self.compact_ratio as usize;
+        let size = self.capacity();
+        let threshold = size / 100 * ratio + size % 100 * ratio / 100;
+        self.tail_free() < len
+            && self.buff_start != 0
+            && (self.buff_start >= threshold || self.tail_free() == 0)
+    }
+
     /// How much data is stored in the buffer
     pub fn used(&self) -> usize {
         self.buff_end - self.buff_start
     }
 
+    /// How much data is stored in the buffer as the `int` length `ThreePM` takes, or None if it doesn't fit
+    pub fn used_c_int(&self) -> Option<i32> {
+        i32::try_from(self.used()).ok()
+    }
+
+    /// `ThreePM` reports how many bytes of the data it was given are left after decoding.
+    /// Move the start of the data past the bytes it consumed, and return how many that was.
+    /// `remaining` is clamped to the data in the buffer so a bad length can't move the start out of bounds
+    pub fn consume_remaining(&mut self, remaining: i32) -> usize {
+        let used = self.used();
+        let remaining = usize::try_from(remaining).unwrap_or(0).min(used);
+        let consumed = used - remaining;
+        self.increment_start(consumed);
+        consumed
+    }
+
     /// How much free space is in the buffer
     pub fn available(&self) -> usize {
-        BUFF_SZ - self.used()
+        self.capacity() - self.used()
     }
 
     /// How much contiguous free space there is at the end of the buffer
     pub fn tail_free(&self) -> usize {
-        BUFF_SZ - self.buff_end
+        self.capacity() - self.buff_end
     }
 
     /// Shuffle all bytes along so that start of buffer == start of data
     pub fn remove_unused(&mut self) {
         if self.buff_start != 0 {
-            self.mp3_byte_buffer
-                .copy_within((self.buff_start)..(self.buff_end), 0);
+            let (start, end) = (self.buff_start, self.buff_end);
+            self.mp3_byte_buffer.as_mut_slice().copy_within(start..end, 0);
             let used = self.used();
             self.buff_start = 0;
             self.buff_end = used;
@@ -67,8 +146,9 @@ impl Buffer {
         }
         while self.available() >= CHUNK_SZ {
             if let Some(d) = loader.next() {
-                let newend = self.buff_end + d.len();
-                self.mp3_byte_buffer[(self.buff_end)..(newend)].copy_from_slice(d);
+                let end = self.buff_end;
+                let newend = end + d.len();
+                self.mp3_byte_buffer.as_mut_slice()[end..newend].copy_from_slice(d);
                 self.buff_end = newend;
                 loaded_some = true;
             } else {
@@ -78,20 +158,41 @@ impl Buffer {
         loaded_some
     }
 
+    /// Whether loading `len` bytes with `load_slice` will shuffle the existing data to the start of the buffer
+    pub fn will_shuffle_on_load(&self, len: usize) -> bool {
+        self.should_compact(len)
+    }
+
     /// Using the provided slice, load more data into the buffer.
     /// Returns the number of bytes consumed
     pub fn load_slice(&mut self, data: &[u8]) -> usize {
-        if self.tail_free() < data.len() {
+        if self.should_compact(data.len()) {
             self.remove_unused();
         }
         let loadsize = usize::min(self.tail_free(), data.len());
-        let newend = self.buff_end + loadsize;
-        self.mp3_byte_buffer[(self.buff_end)..(newend)].copy_from_slice(&data[0..loadsize]);
+        let end = self.buff_end;
+        let newend = end + loadsize;
+        self.mp3_byte_buffer.as_mut_slice()[end..newend].copy_from_slice(&data[0..loadsize]);
         self.buff_end = newend;
 
         loadsize
     }
 
+    /// Return all of the free space in the buffer as a slice at the end so it can be filled in place.
+    /// Data is shuffled to the start first if required. Call `increment_end` with the number of bytes written
+    pub fn tail_slice_mut(&mut self) -> &mut [u8] {
+        if self.should_compact(self.available()) {
+            self.remove_unused();
+        }
+        let end = self.buff_end;
+        &mut self.mp3_byte_buffer.as_mut_slice()[end..]
+    }
+
+    /// Increment our "end pointer". use this after writing data into the slice from `tail_slice_mut`
+    pub fn increment_end(&mut self, increment: usize) {
+        self.buff_end += increment;
+    }
+
     /// Increment our "start pointer". use this as you consume slices from the start
     pub fn increment_start(&mut self, increment: usize) {
         self.buff_start += increment;
@@ -99,7 +200,7 @@ impl Buffer {
 
     /// Return a slice over the remaining data in the buffer
     pub fn borrow_slice(&self) -> &[u8] {
-        &self.mp3_byte_buffer[self.buff_start..self.buff_end]
+        &self.mp3_byte_buffer.as_slice()[self.buff_start..self.buff_end]
     }
 
     /// Return a slice over the remaining data in the buffer and update the indexes
@@ -110,7 +211,7 @@ impl Buffer {
         self.buff_start = 0;
         self.buff_end = 0;
 
-        &self.mp3_byte_buffer[start..end]
+        &self.mp3_byte_buffer.as_slice()[start..end]
     }
 
     /// Return a slice over some of the data and update the indexes
@@ -123,7 +224,7 @@ impl Buffer {
             // update the start of data index to be beyond what we returned
             self.buff_start = end;
 
-            Ok(&self.mp3_byte_buffer[start..end])
+            Ok(&self.mp3_byte_buffer.as_slice()[start..end])
         } else {
             Err(())
         }
@@ -134,6 +235,8 @@ impl Buffer {
 mod tests {
     use super::*;
 
+    type Buffer = super::Buffer<BUFF_SZ>;
+
     #[test]
     fn initial_state_good() {
         let buffer = Buffer::new();
@@ -333,4 +436,96 @@ mod tests {
         // the last 4 bytes should be 69s
         assert_eq!(&data[BUFF_SZ - 8..BUFF_SZ - 4], &[69; 4]);
     }
+
+    #[test]
+    fn predict_shuffle() {
+        let mut buffer = Buffer::new();
+        buffer.load_slice(&[42; BUFF_SZ - 4]);
+        // nothing to shuffle when the data is already at the start
+        assert!(!buffer.will_shuffle_on_load(8));
+        buffer.increment_start(4);
+        assert!(!buffer.will_shuffle_on_load(4));
+        assert!(buffer.will_shuffle_on_load(8));
+        buffer.load_slice(&[69; 8]);
+        assert_eq!(buffer.buff_start, 0);
+    }
+
+    #[test]
+    fn compact_at_threshold() {
+        let mut buffer = Buffer::new();
+        buffer.set_compact_ratio(50);
+        buffer.load_slice(&[42; BUFF_SZ - 8]);
+        buffer.increment_start(16);
+        // not enough has been consumed to shuffle, so only the tail is filled
+        assert_eq!(buffer.load_slice(&[69; 16]), 8);
+        assert_eq!(buffer.buff_start, 16);
+        assert_eq!(buffer.used_contiguous(), BUFF_SZ);
+        // the tail is full, so we have to shuffle
+        assert!(buffer.will_shuffle_on_load(8));
+        assert_eq!(buffer.load_slice(&[69; 8]), 8);
+        assert_eq!(buffer.buff_start, 0);
+        assert_eq!(buffer.used_contiguous(), BUFF_SZ - 8);
+        // past the threshold we shuffle as soon as the tail is too small
+        buffer.increment_start(BUFF_SZ / 2);
+        assert!(buffer.will_shuffle_on_load(16));
+        assert_eq!(buffer.load_slice(&[69; 16]), 16);
+        assert_eq!(buffer.buff_start, 0);
+    }
+
+    #[test]
+    fn caller_storage() {
+        static mut STORAGE: [u8; 16] = [0; 16];
+        // SAFETY: this is the only reference to STORAGE
+        let storage = unsafe { &mut *core::ptr::addr_of_mut!(STORAGE) };
+        let mut buffer = super::Buffer::<0>::with_storage(storage);
+        assert_eq!(buffer.capacity(), 16);
+        assert_eq!(buffer.load_slice(&[42; 20]), 16);
+        assert_eq!(buffer.available(), 0);
+        buffer.increment_start(8);
+        assert_eq!(buffer.load_slice(&[69; 4]), 4);
+        assert_eq!(buffer.borrow_slice(), &[42, 42, 42, 42, 42, 42, 42, 42, 69, 69, 69, 69]);
+    }
+
+    #[test]
+    fn c_int_lengths() {
+        let mut buffer = super::Buffer::<0>::new();
+        // only the indexes matter here, no data is read
+        buffer.buff_end = i32::MAX as usize;
+        assert_eq!(buffer.used_c_int(), Some(i32::MAX));
+        buffer.buff_end += 1;
+        assert_eq!(buffer.used_c_int(), None);
+        buffer.buff_start = 1;
+        assert_eq!(buffer.used_c_int(), Some(i32::MAX));
+    }
+
+    #[test]
+    fn consume_remaining_data() {
+        let mut buffer = Buffer::new();
+        buffer.load_slice(&[42; 100]);
+        assert_eq!(buffer.consume_remaining(60), 40);
+        assert_eq!(buffer.used(), 60);
+        // more remaining than we have means nothing was consumed
+        assert_eq!(buffer.consume_remaining(100), 0);
+        assert_eq!(buffer.used(), 60);
+        assert_eq!(buffer.consume_remaining(i32::MAX), 0);
+        // a negative length can't consume more than we have
+        assert_eq!(buffer.consume_remaining(-1), 60);
+        assert_eq!(buffer.used(), 0);
+    }
+
+    #[test]
+    fn fill_tail_in_place() {
+        let mut buffer = Buffer::new();
+        buffer.load_slice(&[42; BUFF_SZ]);
+        buffer.increment_start(8);
+        // taking the tail should shuffle so that all free space is usable
+        let tail = buffer.tail_slice_mut();
+        assert_eq!(tail.len(), 8);
+        tail[0..4].copy_from_slice(&[69; 4]);
+        buffer.increment_end(4);
+        assert_eq!(buffer.used(), BUFF_SZ - 4);
+        assert_eq!(buffer.tail_free(), 4);
+        let data = buffer.borrow_slice();
+        assert_eq!(&data[BUFF_SZ - 8..BUFF_SZ - 4], &[69; 4]);
+    }
 }
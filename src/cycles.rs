@@ -0,0 +1,57 @@
+//! Timing source used to measure decode times when the `bench` feature is enabled.
+//!
+//! On bare-metal ARM this reads the DWT cycle counter, which the application must enable
+//! (eg with `cortex_m::peripheral::DWT::enable_cycle_counter`). ARMv6-M parts don't have one.
+//! With the `std` feature elsewhere this measures nanoseconds using [std::time::Instant].
+//! On any other target the measurement is always 0.
+
+/// Measures the time elapsed since it was started
+pub(crate) struct Stopwatch {
+    #[cfg(all(target_arch = "arm", target_os = "none"))]
+    start: u32,
+    #[cfg(all(feature = "std", not(all(target_arch = "arm", target_os = "none"))))]
+    start: std::time::Instant,
+}
+
+impl Stopwatch {
+    #[cfg(all(target_arch = "arm", target_os = "none"))]
+    pub fn start() -> Self {
+        Self { start: dwt_cyccnt() }
+    }
+
+    #[cfg(all(target_arch = "arm", target_os = "none"))]
+    pub fn elapsed(&self) -> u64 {
+        // the counter is only 32 bits, wrapping_sub handles a single overflow during measurement
+        dwt_cyccnt().wrapping_sub(self.start) as u64
+    }
+
+    #[cfg(all(feature = "std", not(all(target_arch = "arm", target_os = "none"))))]
+    pub fn start() -> Self {
+        Self {
+            start: std::time::Instant::now(),
+        }
+    }
+
+    #[cfg(all(feature = "std", not(all(target_arch = "arm", target_os = "none"))))]
+    pub fn elapsed(&self) -> u64 {
+        self.start.elapsed().as_nanos() as u64
+    }
+
+    #[cfg(not(any(feature = "std", all(target_arch = "arm", target_os = "none"))))]
+    pub fn start() -> Self {
+        Self {}
+    }
+
+    #[cfg(not(any(feature = "std", all(target_arch = "arm", target_os = "none"))))]
+    pub fn elapsed(&self) -> u64 {
+        0
+    }
+}
+
+/// Read the DWT CYCCNT register
+#[cfg(all(target_arch = "arm", target_os = "none"))]
+fn dwt_cyccnt() -> u32 {
+    const DWT_CYCCNT: *const u32 = 0xE000_1004 as *const u32;
+    // Safety: CYCCNT is a read-only, always-mapped register on ARMv7-M and ARMv8-M
+    unsafe { core::ptr::read_volatile(DWT_CYCCNT) }
+}
@@ -0,0 +1,123 @@
+//! A streaming decoder that owns its data source, for the common "decode this [Read] to the end" case.
+use crate::easy_mode::{EasyMode, EasyModeErr};
+use crate::mp3::MAX_OUTPUT_SAMPLES;
+use std::io::{ErrorKind, Read};
+use std::vec::Vec;
+
+/// Decodes MP3 data pulled from a [Read] one frame at a time.
+///
+/// Each call to `next()` yields the samples of one decoded frame, reading more data from the source
+/// whenever the decoder runs out. Iteration ends at the end of the stream, or after the first error.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// let file = std::fs::File::open("gs-16b-2c-44100hz.mp3")?;
+/// let samples: Vec<i16> = threepm::decoder::Decoder::new(file).flatten().flatten().collect();
+/// # Ok(())
+/// # }
+/// ```
+pub struct Decoder<R: Read> {
+    easy: EasyMode,
+    reader: R,
+    frame: [i16; MAX_OUTPUT_SAMPLES],
+    ready: bool,
+    eof: bool,
+    done: bool,
+}
+
+impl<R: Read> Decoder<R> {
+    /// Construct a new decoder that reads MP3 data from `reader`
+    pub fn new(reader: R) -> Self {
+        Self {
+            easy: EasyMode::new(),
+            reader,
+            frame: [0; MAX_OUTPUT_SAMPLES],
+            ready: false,
+            eof: false,
+            done: false,
+        }
+    }
+
+    /// Access the underlying EasyMode decoder, eg to query [EasyMode::mp3_info]
+    pub fn easy_mode(&mut self) -> &mut EasyMode {
+        &mut self.easy
+    }
+
+    /// Give back the data source
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read more data into the decode buffer. Returns false if nothing more could be read
+    fn fill(&mut self) -> Result<bool, EasyModeErr> {
+        if self.eof {
+            return Ok(false);
+        }
+        loop {
+            match self.easy.fill_from(&mut self.reader) {
+                Ok(0) if self.easy.buffer_free() > 0 => {
+                    self.eof = true;
+                    return Ok(false);
+                }
+                Ok(read) => return Ok(read > 0),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return Err(EasyModeErr::Io),
+            }
+        }
+    }
+
+    /// Decode the next frame into our frame buffer, returning the number of samples or None at end of stream
+    fn next_frame(&mut self) -> Result<Option<usize>, EasyModeErr> {
+        if self.done {
+            return Ok(None);
+        }
+        // skip past the id3 tags and anything else up to the first mp3 sync tag
+        while !self.ready {
+            let more = self.fill()?;
+            self.ready = self.easy.mp3_decode_ready();
+            if !self.ready && !more && self.eof {
+                // nothing more to load, give what's left in the buffer one last try
+                self.ready = self.easy.mp3_decode_ready();
+                if !self.ready {
+                    return Ok(None);
+                }
+            }
+        }
+        loop {
+            if self.easy.buffer_free() > 0 {
+                self.fill()?;
+            }
+            match self.easy.decode(&mut self.frame) {
+                Ok(samples) => return Ok(Some(samples)),
+                Err(EasyModeErr::InDataUnderflow) if !self.eof => {
+                    if !self.fill()? && !self.eof {
+                        // buffer is full and still doesn't hold a frame, so we can never decode it
+                        return Err(EasyModeErr::InDataUnderflow);
+                    }
+                }
+                // Whatever is left at the end of the stream can't be decoded, we're done
+                Err(_) if self.eof => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for Decoder<R> {
+    type Item = Result<Vec<i16>, EasyModeErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.next_frame();
+        match result {
+            Ok(Some(samples)) => Some(Ok(self.frame[..samples].to_vec())),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
@@ -2,23 +2,87 @@
 
 #![deny(unsafe_op_in_unsafe_fn)]
 use crate::contig_buffer;
-use crate::mp3::{DecodeErr, MP3FrameInfo, Mp3};
+use crate::gain;
+use crate::resample::Resampler;
+use crate::mp3::{
+    BitrateMode, ChannelMode, DecodeErr, DecodedHeader, Layer, MP3FrameInfo, Mp3, MpegVersion,
+    SpecViolation, VbrInfo, MAX_OUTPUT_SAMPLES,
+};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 
-/// A high-level, user friendly Rust abstraction around `ThreePM`
-pub struct EasyMode {
+/// A high-level, user friendly Rust abstraction around `ThreePM`, using the default 1024 byte MP3 stream buffer
+pub type EasyMode = EasyModeN<1024>;
+
+/// The largest MP3 stream buffer EasyMode can use, as `ThreePM` takes buffer lengths as an `i32`
+pub const MAX_BUFFER_SIZE: usize = i32::MAX as usize;
+
+/// How many samples the decoder delays its output by, on top of any delay added by the encoder
+const DECODER_DELAY: u32 = 529;
+
+/// A high-level, user friendly Rust abstraction around `ThreePM`, with an `N` byte MP3 stream buffer.
+///
+/// Use a larger buffer if you need to decode high bitrate streams, where a frame plus the bit reservoir
+/// it refers to may not fit into the default buffer size of [EasyMode].
+/// `N` must be no larger than [MAX_BUFFER_SIZE].
+/// To provide the buffer yourself instead, see [with_buffer](Self::with_buffer).
+pub struct EasyModeN<const N: usize> {
     mp3: Mp3,
-    buffer: contig_buffer::Buffer,
+    buffer: contig_buffer::Buffer<N>,
     sync: bool,
     have_decoded: bool,
     parsed_id3: bool,
     bytes_to_skip: usize,
     frame_info: Option<MP3FrameInfo>,
+    max_output_samples: usize,
+    replaygain: Option<ReplayGainMode>,
+    replaygain_checked: bool,
+    gain_q16: u32,
+    channel_gain_q8: (u16, u16),
+    clip_count: u32,
+    track_clipping: bool,
+    last_frame_peaks: (i16, i16),
+    samples_decoded: u64,
+    samples_emitted: u64,
+    last_sync_discard: usize,
+    skipping_id3: bool,
+    loop_start: Option<u64>,
+    loop_end: Option<u64>,
+    loop_length: Option<u64>,
+    looping: bool,
+    loop_restart: bool,
+    discard_until: u64,
+    strict: bool,
+    skip_bad_crc: bool,
+    bad_crc_frames: u32,
+    last_consumed: usize,
+    last_frame_decoded: bool,
+    trim: Option<(u32, u32)>,
+    trim_checked: bool,
+    trim_start: u64,
+    trim_end: Option<u64>,
+    trim_encoder_delay: bool,
+    vbr_info: Option<VbrInfo>,
+    vbr_checked: bool,
+    source: Option<fn(&mut [u8]) -> usize>,
+    cycle_counter: Option<fn() -> u32>,
+    cycles_last_decode: u32,
+    frame_callback: Option<fn(&MP3FrameInfo)>,
+    scratch: Option<&'static mut [i16]>,
+    report_format_changes: bool,
+    output_format: Option<(i32, i32)>,
+    resampler: Resampler,
+    #[cfg(feature = "bench")]
+    last_frame_cycles: u64,
+    #[cfg(feature = "bench")]
+    max_frame_cycles: u64,
 }
 
-impl EasyMode {
+impl<const N: usize> EasyModeN<N> {
     /// Construct a new "easy mode" MP3 decoder
     pub const fn new() -> Self {
-        EasyMode {
+        debug_assert!(N <= MAX_BUFFER_SIZE, "EasyMode buffer is too large");
+        EasyModeN {
             mp3: Mp3::new(),
             buffer: contig_buffer::Buffer::new(),
             sync: false,
@@ -26,47 +90,347 @@ impl EasyMode {
             parsed_id3: false,
             bytes_to_skip: 0,
             frame_info: None,
+            max_output_samples: 0,
+            replaygain: None,
+            replaygain_checked: false,
+            gain_q16: gain::UNITY_Q16,
+            channel_gain_q8: (256, 256),
+            clip_count: 0,
+            track_clipping: false,
+            last_frame_peaks: (0, 0),
+            samples_decoded: 0,
+            samples_emitted: 0,
+            last_sync_discard: 0,
+            skipping_id3: false,
+            loop_start: None,
+            loop_end: None,
+            loop_length: None,
+            looping: false,
+            loop_restart: false,
+            discard_until: 0,
+            strict: false,
+            skip_bad_crc: false,
+            bad_crc_frames: 0,
+            last_consumed: 0,
+            last_frame_decoded: false,
+            trim: None,
+            trim_checked: false,
+            trim_start: 0,
+            trim_end: None,
+            trim_encoder_delay: false,
+            vbr_info: None,
+            vbr_checked: false,
+            source: None,
+            cycle_counter: None,
+            cycles_last_decode: 0,
+            frame_callback: None,
+            scratch: None,
+            report_format_changes: false,
+            output_format: None,
+            resampler: Resampler::new(),
+            #[cfg(feature = "bench")]
+            last_frame_cycles: 0,
+            #[cfg(feature = "bench")]
+            max_frame_cycles: 0,
+        }
+    }
+
+    /// Construct a new "easy mode" MP3 decoder that uses `storage` as its MP3 stream buffer, eg to place the buffer
+    /// in a particular memory region. The buffer is `storage.len()` bytes, which must be no larger than
+    /// [MAX_BUFFER_SIZE], and the inline `N` byte buffer is unused so use `EasyModeN::<0>` to avoid reserving it
+    pub const fn with_buffer(storage: &'static mut [u8]) -> Self {
+        debug_assert!(storage.len() <= MAX_BUFFER_SIZE, "EasyMode buffer is too large");
+        let mut easy = Self::new();
+        easy.buffer = contig_buffer::Buffer::with_storage(storage);
+        easy
+    }
+
+    /// Construct a new "easy mode" MP3 decoder that uses `scratch` as its `i16` scratch buffer.
+    ///
+    /// The decode variants that convert or don't keep the decoded samples ([decode_f32](Self::decode_f32),
+    /// [decode_u8](Self::decode_u8), [decode_planar](Self::decode_planar),
+    /// [decode_resampled](Self::decode_resampled), [decode_discard](Self::decode_discard),
+    /// `decode_into_producer`, [warm_start](Self::warm_start) and the `b` stream of [crossfade]) decode
+    /// into a scratch buffer first, which makes them slightly slower than [decode](Self::decode).
+    /// If no scratch buffer is given they use a 4.5KiB buffer on the stack, which is a lot on embedded
+    /// targets, so give one there to control where that memory lives.
+    /// `scratch` must hold at least [MAX_OUTPUT_SAMPLES] (2304) samples, or frames larger than it return
+    /// [EasyModeErr::AudioBufferTooSmall]. See also [set_scratch](Self::set_scratch)
+    pub const fn with_scratch(scratch: &'static mut [i16]) -> Self {
+        debug_assert!(scratch.len() >= MAX_OUTPUT_SAMPLES, "EasyMode scratch is too small");
+        let mut easy = Self::new();
+        easy.scratch = Some(scratch);
+        easy
+    }
+
+    /// Use `scratch` as the `i16` scratch buffer like [with_scratch](Self::with_scratch), eg for a decoder
+    /// constructed with [with_buffer](Self::with_buffer). It must hold at least [MAX_OUTPUT_SAMPLES] samples
+    pub fn set_scratch(&mut self, scratch: &'static mut [i16]) {
+        debug_assert!(scratch.len() >= MAX_OUTPUT_SAMPLES, "EasyMode scratch is too small");
+        self.scratch = Some(scratch);
+    }
+
+    /// Reset the decoder so it can be reused for a new track, without the cost of constructing a new one.
+    ///
+    /// This empties the MP3 stream buffer, forgets the stream position, tags and frame info of the
+    /// previous track and clears the decoder's history so the old track doesn't bleed into the new one.
+    /// Settings such as ReplayGain mode, channel gains, clip tracking, looping, strict mode, CRC skipping,
+    /// [encoder delay trimming](Self::set_trim_encoder_delay), the [data source](Self::set_source) and
+    /// the [cycle counter](Self::set_cycle_counter) and the [frame callback](Self::on_frame) are kept, but
+    /// [set_trim](Self::set_trim) is cleared as it depends on the track
+    pub fn reset(&mut self) {
+        self.mp3.reset();
+        let _ = self.buffer.take_slice();
+        self.sync = false;
+        self.have_decoded = false;
+        self.parsed_id3 = false;
+        self.bytes_to_skip = 0;
+        self.frame_info = None;
+        self.max_output_samples = 0;
+        self.replaygain_checked = false;
+        self.gain_q16 = gain::UNITY_Q16;
+        self.clip_count = 0;
+        self.last_frame_peaks = (0, 0);
+        self.samples_decoded = 0;
+        self.samples_emitted = 0;
+        self.last_sync_discard = 0;
+        self.skipping_id3 = false;
+        self.loop_start = None;
+        self.loop_end = None;
+        self.loop_length = None;
+        self.loop_restart = false;
+        self.discard_until = 0;
+        self.bad_crc_frames = 0;
+        self.last_consumed = 0;
+        self.last_frame_decoded = false;
+        self.trim = None;
+        self.trim_checked = false;
+        self.trim_start = 0;
+        self.trim_end = None;
+        self.vbr_info = None;
+        self.vbr_checked = false;
+        self.cycles_last_decode = 0;
+        self.output_format = None;
+        self.resampler = Resampler::new();
+        #[cfg(feature = "bench")]
+        {
+            self.last_frame_cycles = 0;
+            self.max_frame_cycles = 0;
         }
     }
 
     /// Add MP3 data to the EasyMode internal MP3 stream buffer.
+    /// Returns how many bytes were added, which is less than `data.len()` if the buffer is full
     pub fn add_data(&mut self, data: &[u8]) -> usize {
         self.buffer.load_slice(data)
     }
 
+    /// Add MP3 data to the EasyMode internal MP3 stream buffer like [add_data](Self::add_data), but return
+    /// `Err(accepted)` if the buffer couldn't hold all of `data`. The first `accepted` bytes were added, so
+    /// add the rest of `data` once some of the buffer has been decoded
+    pub fn try_add_data(&mut self, data: &[u8]) -> Result<(), usize> {
+        let accepted = self.add_data(data);
+        if accepted == data.len() {
+            Ok(())
+        } else {
+            Err(accepted)
+        }
+    }
+
     /// Every mp3 frame starts with a sync word. Skip any data in buffer until the next sync word, and check if it's a valid frame.
+    /// Sync words followed by an invalid frame header are false positives, and are skipped over.
     /// Returns true if it found a sync word, otherwise false
     pub fn skip_to_next_sync_word(&mut self) -> bool {
         if !self.sync {
-            let start = Mp3::find_sync_word(self.buffer.borrow_slice());
-            if start >= 0 {
-                self.buffer.increment_start(start as usize);
-                self.sync = true;
+            let (sync, discarded) = sync_to_frame(&mut self.buffer);
+            self.sync = sync;
+            self.last_sync_discard = discarded;
+            if self.sync {
                 // Also try to get frame info for next frame
                 let f = self.mp3.get_next_frame_info(self.buffer.borrow_slice());
                 if let Ok(frame) = f {
-                    self.frame_info = Some(frame);
+                    self.set_frame_info(frame);
                     self.have_decoded = true;
                 }
-            } else {
-                // Could not sync with any of the data in the buffer, so most of the data is useless.
-                // we could have 3 bytes of sync word, so keep the last 3 bytes
-                self.buffer.increment_start(self.buffer.used() - 3);
             }
         }
         self.sync
     }
 
+    /// Skip to the next sync word like [skip_to_next_sync_word](Self::skip_to_next_sync_word), returning how many
+    /// bytes were skipped before it, eg to diagnose streams with lots of padding or junk between frames.
+    /// Returns `Ok(0)` if the buffer is already synced, and `Err(())` if no sync word was found. Use
+    /// [last_sync_discard](Self::last_sync_discard) to see how much was thrown away when it wasn't found
+    #[allow(clippy::result_unit_err)]
+    pub fn skip_to_next_sync_word_counted(&mut self) -> Result<usize, ()> {
+        let was_synced = self.sync;
+        if !self.skip_to_next_sync_word() {
+            Err(())
+        } else if was_synced {
+            Ok(0)
+        } else {
+            Ok(self.last_sync_discard)
+        }
+    }
+
+    /// Recover from a run of decode errors, eg after a glitch in an internet radio stream.
+    ///
+    /// This skips past the frame at the start of the buffer to the next sync word with a valid frame header.
+    /// If that frame is protected by a CRC and is completely buffered, its CRC must match too.
+    /// The decoder's history is cleared so the bad data doesn't leak into the frames after it, and the first
+    /// of those may be dropped while the bit reservoir refills.
+    /// Returns true if it found a frame to resume decoding from, otherwise add more data and call it again
+    pub fn recover(&mut self) -> bool {
+        self.mp3.reset();
+        if self.sync && self.buffer.used() > 0 {
+            // step past the start of the bad frame so that we find the next one
+            self.buffer.increment_start(1);
+        }
+        self.sync = false;
+        while self.skip_to_next_sync_word() {
+            if Mp3::verify_frame_crc(self.buffer.borrow_slice()) != Some(false) {
+                return true;
+            }
+            self.buffer.increment_start(1);
+            self.sync = false;
+        }
+        false
+    }
+
+    /// Read MP3 data from `reader` directly into the EasyMode internal MP3 stream buffer.
+    /// Returns the number of bytes read, which is 0 at end of stream or if the buffer is full
+    #[cfg(feature = "std")]
+    pub fn fill_from<R: std::io::Read>(&mut self, reader: &mut R) -> std::io::Result<usize> {
+        let tail = self.buffer.tail_slice_mut();
+        if tail.is_empty() {
+            return Ok(0);
+        }
+        // don't trust the reader to stay within the slice it was given
+        let read = reader.read(tail)?.min(tail.len());
+        self.buffer.increment_end(read);
+        Ok(read)
+    }
+
+    /// Register a data source that [decode](Self::decode) calls to top up the MP3 stream buffer, so that
+    /// decoding a stream is just repeated calls to `decode`. This also skips the ID3 tags and anything else
+    /// before the first frame, as [mp3_decode_ready](Self::mp3_decode_ready) does.
+    ///
+    /// `source` is passed the free space at the end of the buffer and returns how many bytes it wrote there,
+    /// with 0 meaning it has no more data for now. It is a function pointer so that it can be stored without
+    /// an allocator. To read from a source with state, keep the state in a static, or use
+    /// [Frames::source] instead. The source is kept by [reset](Self::reset)
+    pub fn set_source(&mut self, source: fn(&mut [u8]) -> usize) {
+        self.source = Some(source);
+    }
+
+    /// Stop topping up the MP3 stream buffer from the source registered with [set_source](Self::set_source)
+    pub fn clear_source(&mut self) {
+        self.source = None;
+    }
+
+    /// Top up the MP3 stream buffer from the registered source as far as it will fit.
+    /// Returns the number of bytes added
+    fn fill_from_source(&mut self) -> usize {
+        let Some(source) = self.source else {
+            return 0;
+        };
+        let mut added = 0;
+        while self.buffer_free() > 0 {
+            let tail = self.buffer.tail_slice_mut();
+            let read = source(tail).min(tail.len());
+            if read == 0 {
+                break;
+            }
+            self.buffer.increment_end(read);
+            added += read;
+        }
+        added
+    }
+
+    /// Top up the MP3 stream buffer from the registered source, and skip to the first frame of the stream
+    fn refill(&mut self) {
+        if self.source.is_none() {
+            return;
+        }
+        self.fill_from_source();
+        while !self.mp3_decode_ready() {
+            if self.fill_from_source() == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Top up the EasyMode internal MP3 stream buffer from `reader` as far as it will fit,
+    /// then decode the next MP3 audio frame like [decode](Self::decode).
+    /// Errors from the reader are returned as [EasyModeErr::Io]
+    #[cfg(feature = "embedded-io")]
+    pub fn decode_from<R: embedded_io::Read>(
+        &mut self,
+        reader: &mut R,
+        output_audio: &mut [i16],
+    ) -> Result<usize, EasyModeErr> {
+        while self.buffer_free() > 0 {
+            let tail = self.buffer.tail_slice_mut();
+            let read = reader.read(tail).map_err(|_| EasyModeErr::Io)?.min(tail.len());
+            if read == 0 {
+                break;
+            }
+            self.buffer.increment_end(read);
+        }
+        self.decode(output_audio)
+    }
+
+    /// How many bytes the most recent sync attempt in [skip_to_next_sync_word](Self::skip_to_next_sync_word) threw away.
+    ///
+    /// When no sync word was found this is everything but the few bytes kept in case they're the start of one.
+    /// Repeatedly large discards on a stream are a sign that it is unhealthy
+    pub fn last_sync_discard(&self) -> usize {
+        self.last_sync_discard
+    }
+
     /// How much data is free in the EasyMode internal MP3 stream buffer
     pub fn buffer_free(&self) -> usize {
         self.buffer.available()
     }
 
+    /// How much contiguous free space there is at the end of the EasyMode internal MP3 stream buffer.
+    /// Data can be written here in place (eg by DMA) as long as adding it doesn't cause a shuffle,
+    /// see [will_shuffle_on_add](Self::will_shuffle_on_add)
+    pub fn tail_contiguous_free(&self) -> usize {
+        self.buffer.tail_free()
+    }
+
+    /// Whether adding `len` bytes will shuffle the buffered data to the start of the buffer.
+    /// A shuffle moves the free space at the end of the buffer, invalidating any pointer to it
+    pub fn will_shuffle_on_add(&self, len: usize) -> bool {
+        self.buffer.will_shuffle_on_load(len)
+    }
+
+    /// Only shuffle the buffered data to the start of the buffer once `percent` of the buffer has been consumed,
+    /// or when there is no space left at the end of it, to reduce how often data is moved during streaming.
+    ///
+    /// The default of 0 shuffles whenever the space at the end of the buffer is too small for new data.
+    /// Above that, [add_data](Self::add_data) may accept less than [buffer_free](Self::buffer_free) bytes, so
+    /// check how much it returns
+    pub fn set_compact_ratio(&mut self, percent: u8) {
+        self.buffer.set_compact_ratio(percent);
+    }
+
     /// How much MP3 data is in the EasyMode internal MP3 stream buffer
     pub fn buffer_used(&self) -> usize {
         self.buffer.used()
     }
 
+    /// How full the EasyMode internal MP3 stream buffer is, all read at once, eg for a fill gauge
+    pub fn buffer_stats(&self) -> BufferStats {
+        BufferStats {
+            used: self.buffer.used(),
+            free: self.buffer.available(),
+            contiguous_free: self.buffer.tail_free(),
+            capacity: self.buffer.capacity(),
+        }
+    }
+
     /// Skip over data in the buffer without decoding it
     pub fn buffer_skip(&mut self, count: usize) -> usize {
         let to_remove = core::cmp::min(self.buffer.used(), count);
@@ -74,6 +438,18 @@ impl EasyMode {
         to_remove
     }
 
+    /// Throw away all of the data in the buffer, eg when switching streams or seeking.
+    /// The decoder will need to find a sync word again before decoding
+    pub fn buffer_clear(&mut self) {
+        let _ = self.buffer.take_slice();
+        self.sync = false;
+    }
+
+    /// Check whether there is a sync word anywhere in the buffer, without changing anything
+    pub fn buffer_contains_sync(&self) -> bool {
+        Mp3::find_sync_word_offset(self.buffer.borrow_slice()).is_some()
+    }
+
     /// Skip over ID3 and anything else at the start of an MP3 stream.
     /// Returns true when we've got a valid MP3 frame
     pub fn mp3_decode_ready(&mut self) -> bool {
@@ -81,19 +457,21 @@ impl EasyMode {
             false
         } else {
             if !self.parsed_id3 {
-                self.parsed_id3 = true;
-                let id3 = Mp3::find_id3v2(self.buffer.borrow_slice());
-                self.bytes_to_skip = if let Some(id3) = id3 {
-                    // start of header + size of header + length of id3v2 info
-                    id3.0 + 10 + id3.1.size
-                } else {
-                    0
+                let Some(bytes_to_skip) = id3v2_skip_len(&self.buffer) else {
+                    return false;
                 };
+                self.parsed_id3 = true;
+                self.skipping_id3 = bytes_to_skip > 0;
+                self.bytes_to_skip = bytes_to_skip;
             };
             if self.bytes_to_skip > 0 {
                 let bytes_to_skip = core::cmp::min(self.buffer_used(), self.bytes_to_skip);
+                if self.skipping_id3 {
+                    self.find_loop_tags(bytes_to_skip);
+                }
                 self.buffer_skip(bytes_to_skip);
                 self.bytes_to_skip -= bytes_to_skip;
+                self.skipping_id3 &= self.bytes_to_skip > 0;
             } else {
                 let _ = self.skip_to_next_sync_word();
             }
@@ -102,110 +480,2206 @@ impl EasyMode {
         }
     }
 
+    /// Iterate over the frames of the stream, decoding each one into `output_audio`. See [Frames]
+    pub fn frames<'a>(&'a mut self, output_audio: &'a mut [i16]) -> Frames<'a, N> {
+        Frames {
+            easy: self,
+            output: output_audio,
+            source: core::iter::empty(),
+            pending: &[],
+            samples: 0,
+            eof: false,
+            done: false,
+        }
+    }
+
     /// Decode the next MP3 audio frame after checking that the output buffer is large enough
+    ///
+    /// When decoding starts part way through a stream (eg joining an internet radio stream), the first few frames
+    /// can use bit reservoir data from earlier frames that were never decoded. These frames are still decoded to
+    /// fill the bit reservoir, but their output is garbage so it is dropped and `Ok(0)` is returned
     pub fn decode(&mut self, output_audio: &mut [i16]) -> Result<usize, EasyModeErr> {
-        let buffered_data_len = self.buffer.used() as i32;
-        let oldlen = buffered_data_len as usize;
-        let next_frame = self.mp3.get_next_frame_info(self.buffer.borrow_slice())?;
-        let samples = next_frame.outputSamps as usize;
-        if output_audio.len() < samples {
+        self.decode_fitting(output_audio, |_| true)
+    }
+
+    /// Decode the next MP3 audio frame like [decode](Self::decode), only if `fits` also returns true for
+    /// the frame that is about to be decoded, eg to check the size of a caller's buffer that the output
+    /// will be converted into
+    fn decode_fitting(
+        &mut self,
+        output_audio: &mut [i16],
+        fits: impl FnOnce(&MP3FrameInfo) -> bool,
+    ) -> Result<usize, EasyModeErr> {
+        let buffered_data_len = self.start_decode()?;
+        let next_frame = self.next_frame_info()?;
+        let main_data = self.prepare_frame(Some(&next_frame))?;
+        if output_audio.len() < next_frame.outputSamps.max(0) as usize || !fits(&next_frame) {
             // Don't decode if there isn't enough space in the buffer
-            Err(EasyModeErr::AudioBufferTooSmall)
-        } else {
-            match self
-                .mp3
-                .decode(self.buffer.borrow_slice(), buffered_data_len, output_audio)
-            {
-                Ok(newlen) => {
-                    self.have_decoded = true;
-                    let consumed = oldlen - newlen as usize;
-                    self.buffer.increment_start(consumed);
-                    self.frame_info = Some(next_frame);
-                    Ok(samples)
-                }
-                Err(e) => Err(e.into()),
+            return Err(EasyModeErr::AudioBufferTooSmall);
+        }
+        match self.decode_timed(buffered_data_len, output_audio) {
+            Ok(newlen) => Ok(self.finish_frame(next_frame, newlen, main_data, output_audio)),
+            Err(DecodeErr::MaindataUnderfow) if !main_data => Ok(self.drop_frame(next_frame)),
+            Err(e) => Err(self.frame_err(e)),
+        }
+    }
+
+    /// Decode the next MP3 audio frame like [decode](Self::decode), calling `onsets` with the stream
+    /// position (in samples per channel) of each granule where the encoder switched to short blocks.
+    ///
+    /// Encoders use short blocks around transients, so this gives a free onset detector for things
+    /// like beat-reactive lighting. It is a coarse heuristic: how eagerly short blocks are used
+    /// depends entirely on the encoder and its settings, and some encoders never use them at all
+    pub fn decode_with_onsets(
+        &mut self,
+        output_audio: &mut [i16],
+        onsets: &mut impl FnMut(u64),
+    ) -> Result<usize, EasyModeErr> {
+        let frame_start = self.samples_decoded;
+        let samples = self.decode(output_audio)?;
+        let frame_samples = self.samples_decoded - frame_start;
+        let granules = (0..2)
+            .take_while(|granule| self.mp3.block_type(*granule, 0).is_some())
+            .count() as u64;
+        for granule in 0..granules {
+            let short = (0..2)
+                .any(|channel| self.mp3.block_type(granule as usize, channel) == Some(2));
+            if short {
+                onsets(frame_start + granule * frame_samples / granules);
             }
         }
+        Ok(samples)
+    }
+
+    /// Decode the next MP3 audio frame like [decode](Self::decode), also returning the spectral centroid
+    /// of the frame as a subband index from 0 (darkest) to 31 (brightest).
+    ///
+    /// This is a cheap "brightness" measure for visual effects that reuses the decoder's own subband data
+    /// instead of needing an FFT. It is approximate: it only looks at the last granule of the frame,
+    /// and each subband covers a fixed 1/32nd of the bandwidth (about 690Hz at 44.1kHz). Silence gives 0
+    pub fn decode_with_centroid(
+        &mut self,
+        output_audio: &mut [i16],
+    ) -> Result<(usize, u16), EasyModeErr> {
+        let samples = self.decode(output_audio)?;
+        let levels = self.mp3.subband_levels();
+        let total: u64 = levels.iter().sum();
+        let weighted: u64 = levels
+            .iter()
+            .enumerate()
+            .map(|(band, level)| band as u64 * level)
+            .sum();
+        let centroid = weighted.checked_div(total).unwrap_or(0);
+        Ok((samples, centroid as u16))
+    }
+
+    /// Step past the next frame without decoding it, eg for fast-forward scrubbing.
+    /// Returns the number of samples the frame would have decoded to.
+    ///
+    /// Only the frame header is parsed, so this is far cheaper than [decode](Self::decode).
+    /// Returns [EasyModeErr::InDataUnderflow] if the whole frame isn't in the buffer yet.
+    /// The frames following a skipped one may fail to decode if they use its bit reservoir
+    pub fn skip_frame(&mut self) -> Result<usize, EasyModeErr> {
+        let frame = self.peek_next_frame_info()?;
+        if frame.size == 0 {
+            // free format frames don't have their length in the header
+            return Err(EasyModeErr::FreeBitrateSync);
+        }
+        let size = frame.size as usize;
+        if size > self.buffer.used() {
+            return Err(EasyModeErr::InDataUnderflow);
+        }
+        self.buffer.increment_start(size);
+        self.samples_decoded += samples_per_channel(&frame) as u64;
+        self.set_frame_info(frame);
+        Ok(frame.outputSamps as usize)
+    }
+
+    /// Decode the next MP3 audio frame like [decode](Self::decode), also returning how many bytes of MP3 data
+    /// were consumed from the buffer, so that you can track the byte offset into the source stream.
+    ///
+    /// Returns `(samples, bytes_consumed)`. Frames skipped because of a bad CRC are included in the bytes consumed
+    pub fn decode_with_consumed(
+        &mut self,
+        output_audio: &mut [i16],
+    ) -> Result<(usize, usize), EasyModeErr> {
+        let samples = self.decode(output_audio)?;
+        Ok((samples, self.last_consumed))
+    }
+
+    /// Add as much of `input` as fits in the MP3 stream buffer, then try to decode one frame, eg for a
+    /// pull-based reader that hands over whatever data it has.
+    ///
+    /// Returns `(samples, input_bytes_consumed)`. Present the rest of `input` on the next call.
+    /// This also skips ID3v2 tags and syncs to the first frame like
+    /// [mp3_decode_ready](Self::mp3_decode_ready), returning 0 samples until that is done or while there
+    /// isn't enough data for a frame.
+    /// Any other decode error is returned as `(error, input_bytes_consumed)`, as the input was still added
+    pub fn push_and_decode(
+        &mut self,
+        input: &[u8],
+        output_audio: &mut [i16],
+    ) -> Result<(usize, usize), (EasyModeErr, usize)> {
+        let consumed = self.add_data(input);
+        if !self.mp3_decode_ready() {
+            return Ok((0, consumed));
+        }
+        match self.decode(output_audio) {
+            Ok(samples) => Ok((samples, consumed)),
+            Err(EasyModeErr::InDataUnderflow) => Ok((0, consumed)),
+            Err(e) => Err((e, consumed)),
+        }
+    }
+
+    /// Decode the next MP3 audio frame and resample it to `target_rate` Hz, eg for a DAC that only runs
+    /// at 44.1kHz.
+    ///
+    /// This is basic fixed-point linear interpolation between neighbouring samples, not a high quality
+    /// resampler, so expect some aliasing when downsampling. The interpolation carries on across frame
+    /// boundaries so that they don't click, and restarts when the stream's format changes.
+    /// `output_audio` must hold the resampled frame, which is up to
+    /// `(outputSamps / channels * target_rate / sample_rate + 2) * channels` samples, otherwise
+    /// [EasyModeErr::AudioBufferTooSmall] is returned without decoding.
+    /// Uses 4.5KiB of stack for the decoded frame unless a [scratch buffer](Self::with_scratch) is set.
+    /// Returns the number of samples written to `output_audio`
+    pub fn decode_resampled(
+        &mut self,
+        output_audio: &mut [i16],
+        target_rate: u32,
+    ) -> Result<usize, EasyModeErr> {
+        // channels and sample rate of a frame, to resample from
+        let format = |frame: &MP3FrameInfo| {
+            let channels = frame.nChans.max(1) as usize;
+            (channels, frame.samprate.max(0) as u32)
+        };
+        let len = output_audio.len();
+        let fits = |frame: &MP3FrameInfo| {
+            let (channels, sample_rate) = format(frame);
+            let samples = frame.outputSamps.max(0) as usize;
+            len >= Resampler::max_output_len(samples, channels, sample_rate, target_rate)
+        };
+        let mut resampler = self.resampler;
+        let result = self.decode_scratch(fits, |samples, frame| {
+            let (channels, sample_rate) = format(frame);
+            resampler.process(samples, channels, sample_rate, target_rate, output_audio)
+        });
+        self.resampler = resampler;
+        result
+    }
+
+    /// Decode the next MP3 audio frame and downmix it to mono, for single speaker playback.
+    ///
+    /// Stereo frames are decoded as normal and then each pair of samples is averaged in place as
+    /// `(l as i32 + r as i32) / 2`, which can't overflow or clip. Mono frames are passed through unchanged.
+    /// `output_audio` still needs to hold the whole decoded frame.
+    /// Returns the number of mono samples at the start of `output_audio`, half the stereo sample count
+    pub fn decode_mono(&mut self, output_audio: &mut [i16]) -> Result<usize, EasyModeErr> {
+        let samples = self.decode(output_audio)?;
+        if self.frame_info.map(|frame| frame.nChans) != Some(2) {
+            return Ok(samples);
+        }
+        Ok(downmix_to_mono(&mut output_audio[..samples]))
+    }
+
+    /// Decode the next MP3 audio frame and push the samples into a ring buffer, eg one that is drained by an
+    /// audio interrupt. Returns the number of samples pushed.
+    ///
+    /// The frame is only decoded once the ring buffer has space for all of it, otherwise nothing is decoded,
+    /// the MP3 data stays buffered and 0 is returned so that you can try again later.
+    /// Uses 4.5KiB of stack for the decoded frame unless a [scratch buffer](Self::with_scratch) is set
+    #[cfg(feature = "ringbuf")]
+    pub fn decode_into_producer(
+        &mut self,
+        producer: &mut ringbuf::Producer<i16>,
+    ) -> Result<usize, EasyModeErr> {
+        self.check_loop_restart()?;
+        if producer.remaining() < self.required_output_len()? {
+            return Ok(0);
+        }
+        self.decode_scratch(|_| true, |samples, _| producer.push_slice(samples))
+    }
+
+    /// Decode the next MP3 audio frame into normalized `f32` samples in the range -1.0..1.0, for audio
+    /// pipelines that want floating point samples. Each sample is the decoded 16 bit sample divided by 32768.
+    /// Uses 4.5KiB of stack for the decoded frame unless a [scratch buffer](Self::with_scratch) is set.
+    /// Returns [EasyModeErr::AudioBufferTooSmall] if `output_audio` can't hold the whole frame
+    pub fn decode_f32(&mut self, output_audio: &mut [f32]) -> Result<usize, EasyModeErr> {
+        let len = output_audio.len();
+        let fits = |frame: &MP3FrameInfo| len >= frame.outputSamps.max(0) as usize;
+        self.decode_scratch(fits, |samples, _| {
+            for (out, sample) in output_audio.iter_mut().zip(samples) {
+                *out = *sample as f32 / 32768.0;
+            }
+            samples.len()
+        })
+    }
+
+    /// Decode the next MP3 audio frame into unsigned 8 bit samples centered on 128, for PWM or other 8 bit DACs.
+    ///
+    /// Each sample keeps only the top 8 bits of the decoded 16 bit sample, as `(s >> 8) + 128`. This loses a lot
+    /// of dynamic range (about 48dB instead of 96dB) and quiet passages become audibly noisy, so only use it when
+    /// the output can't take anything better.
+    /// Uses 4.5KiB of stack for the decoded frame unless a [scratch buffer](Self::with_scratch) is set.
+    /// Returns [EasyModeErr::AudioBufferTooSmall] if `output_audio` can't hold the whole frame
+    pub fn decode_u8(&mut self, output_audio: &mut [u8]) -> Result<usize, EasyModeErr> {
+        let len = output_audio.len();
+        let fits = |frame: &MP3FrameInfo| len >= frame.outputSamps.max(0) as usize;
+        self.decode_scratch(fits, |samples, _| {
+            for (out, sample) in output_audio.iter_mut().zip(samples) {
+                *out = ((*sample >> 8) + 128) as u8;
+            }
+            samples.len()
+        })
+    }
+
+    /// Decode the next MP3 audio frame into separate left and right channel buffers, for planar DSP chains.
+    ///
+    /// Stereo frames are de-interleaved into `left` and `right`. Mono frames are decoded into `left` only,
+    /// and `right` is left untouched.
+    /// Uses 4.5KiB of stack for the decoded frame unless a [scratch buffer](Self::with_scratch) is set.
+    /// Returns the number of samples per channel, or [EasyModeErr::AudioBufferTooSmall] if a channel buffer
+    /// can't hold a whole frame
+    pub fn decode_planar(
+        &mut self,
+        left: &mut [i16],
+        right: &mut [i16],
+    ) -> Result<usize, EasyModeErr> {
+        let (left_len, right_len) = (left.len(), right.len());
+        let fits = |frame: &MP3FrameInfo| {
+            let samples = samples_per_channel(frame);
+            left_len >= samples && (frame.nChans != 2 || right_len >= samples)
+        };
+        self.decode_scratch(fits, |samples, frame| {
+            if frame.nChans == 2 {
+                let frames = samples.chunks_exact(2);
+                for ((l, r), frame) in left.iter_mut().zip(right.iter_mut()).zip(frames) {
+                    *l = frame[0];
+                    *r = frame[1];
+                }
+                samples.len() / 2
+            } else {
+                left[..samples.len()].copy_from_slice(samples);
+                samples.len()
+            }
+        })
+    }
+
+    /// Decode the next MP3 audio frame and throw the samples away, eg to rebuild the decoder's state after a seek.
+    ///
+    /// You don't need an output buffer of your own, but this uses 4.5KiB of stack for the decoded frame
+    /// unless a [scratch buffer](Self::with_scratch) is set.
+    /// Returns the number of samples that were discarded
+    pub fn decode_discard(&mut self) -> Result<usize, EasyModeErr> {
+        self.decode_scratch(|_| true, |samples, _| samples.len())
+    }
+
+    /// Decode the next MP3 audio frame into the scratch buffer, then pass the decoded samples and the
+    /// frame's info to `output`. The frame is only decoded if `fits` returns true for it, otherwise
+    /// [EasyModeErr::AudioBufferTooSmall] is returned
+    fn decode_scratch<T>(
+        &mut self,
+        fits: impl FnOnce(&MP3FrameInfo) -> bool,
+        output: impl FnOnce(&[i16], &MP3FrameInfo) -> T,
+    ) -> Result<T, EasyModeErr> {
+        let Some(scratch) = self.scratch.take() else {
+            return self.decode_stack_scratch(fits, output);
+        };
+        let result = self.decode_fitting(scratch, fits);
+        let frame = self.frame_info.unwrap_or_default();
+        let result = result.map(|samples| output(&scratch[..samples], &frame));
+        self.scratch = Some(scratch);
+        result
+    }
+
+    /// Decode the next MP3 audio frame into the scratch buffer given to [with_scratch](Self::with_scratch)
+    /// or [set_scratch](Self::set_scratch), and borrow the decoded samples from it without copying them.
+    /// The samples are valid until the next call that decodes.
+    /// Returns [EasyModeErr::AudioBufferTooSmall] if no scratch buffer has been provided
+    pub fn decode_borrowed(&mut self) -> Result<&[i16], EasyModeErr> {
+        let Some(scratch) = self.scratch.take() else {
+            return Err(EasyModeErr::AudioBufferTooSmall);
+        };
+        let result = self.decode(scratch);
+        self.scratch = Some(scratch);
+        let samples = result?;
+        Ok(self.scratch.as_deref().map_or(&[], |scratch| &scratch[..samples]))
+    }
+
+    /// Decode the next MP3 audio frame into a scratch buffer on the stack, then pass the decoded samples
+    /// to `output`. This isn't inlined so that the stack is only used when there's no other scratch buffer
+    #[inline(never)]
+    fn decode_stack_scratch<T>(
+        &mut self,
+        fits: impl FnOnce(&MP3FrameInfo) -> bool,
+        output: impl FnOnce(&[i16], &MP3FrameInfo) -> T,
+    ) -> Result<T, EasyModeErr> {
+        let mut scratch = [0i16; MAX_OUTPUT_SAMPLES];
+        let samples = self.decode_fitting(&mut scratch, fits)?;
+        Ok(output(&scratch[..samples], &self.frame_info.unwrap_or_default()))
+    }
+
+    /// Decode the next MP3 audio frame into one half of a ping-pong (double) buffer, for DMA audio playback.
+    ///
+    /// Decodes into `buf_a` when `which` is false and `buf_b` when it is true, then flips `which`
+    /// on success so that the next call fills the other buffer. Hand the buffer that was just filled to DMA
+    /// while the other one is being decoded into. Both buffers must be large enough for a whole frame
+    pub fn decode_pingpong(
+        &mut self,
+        buf_a: &mut [i16],
+        buf_b: &mut [i16],
+        which: &mut bool,
+    ) -> Result<usize, EasyModeErr> {
+        let output_audio = if *which { buf_b } else { buf_a };
+        let samples = self.decode(output_audio)?;
+        *which = !*which;
+        Ok(samples)
     }
 
     /// Decode the next MP3 audio frame assuming that the output buffer is large enough.
     ///
     /// # Safety
     ///
-    /// Ensure output buffer is larger than your MP3 frame or this will totally ruin your day
+    /// Ensure output buffer is larger than your MP3 frame or this will totally ruin your day.
+    /// [next_frame_output_len](Self::next_frame_output_len) tells you how large it needs to be.
+    ///
+    /// Debug builds check the buffer against the next frame header before decoding, and against the
+    /// number of samples the frame decoded to afterwards, panicking if it is too small. Release builds
+    /// check nothing: `ThreePM` writes a whole frame to `output_audio` whatever its length, so it must
+    /// hold at least `outputSamps` of the frame being decoded, or [MAX_OUTPUT_SAMPLES] to be safe for
+    /// any frame
     pub unsafe fn decode_unchecked(
         &mut self,
         output_audio: &mut [i16],
     ) -> Result<usize, EasyModeErr> {
-        let buffered_data_len = self.buffer.used() as i32;
-        let oldlen = buffered_data_len;
-        match self
-            .mp3
-            .decode(self.buffer.borrow_slice(), buffered_data_len, output_audio)
-        {
+        let buffered_data_len = self.start_decode()?;
+        // parse the header in Rust, asking ThreePM for it would cost another call into the decoder
+        let next_frame = self.peek_next_frame_info().ok();
+        debug_assert!(
+            next_frame.is_none_or(|frame| output_audio.len() >= frame.outputSamps as usize),
+            "output buffer is too small for the next frame"
+        );
+        let main_data = self.prepare_frame(next_frame.as_ref())?;
+        match self.decode_timed(buffered_data_len, output_audio) {
             Ok(newlen) => {
-                self.frame_info = Some(self.mp3.get_last_frame_info());
-                // we just set this so the unwrap should never fail
-                let output_samps = unsafe { self.frame_info.unwrap_unchecked().outputSamps };
-                let consumed = oldlen as usize - newlen as usize;
-                self.buffer.increment_start(consumed);
-                self.have_decoded = true;
-                Ok(output_samps as usize)
+                let frame = self.mp3.get_last_frame_info();
+                debug_assert!(
+                    output_audio.len() >= frame.outputSamps.max(0) as usize,
+                    "output buffer is too small for the decoded frame"
+                );
+                Ok(self.finish_frame(frame, newlen, main_data, output_audio))
             }
-            Err(e) => Err(e.into()),
+            Err(DecodeErr::MaindataUnderfow) if !main_data => {
+                let frame = self.mp3.get_last_frame_info();
+                Ok(self.drop_frame(frame))
+            }
+            Err(e) => Err(self.frame_err(e)),
         }
     }
 
-    /// Get MP3 metadata from the last MP3 frame decoded
-    pub fn mp3_info(&mut self) -> Result<MP3FrameInfo, EasyModeErr> {
-        if let Some(frameinfo) = self.frame_info {
-            Ok(frameinfo)
+    /// Get ready to decode the frame at the start of the buffer: restart a loop if needed, top the buffer
+    /// up from the data source and skip bad CRC frames. Returns how much data is buffered
+    fn start_decode(&mut self) -> Result<i32, EasyModeErr> {
+        self.check_loop_restart()?;
+        self.refill();
+        self.last_consumed = 0;
+        self.last_frame_decoded = false;
+        self.skip_bad_crc_frames();
+        self.buffered_len()
+    }
+
+    /// Check the frame at the start of the buffer before it is decoded, and pick up any stream info that
+    /// hasn't been read yet. `next_frame` is None if its header couldn't be parsed ahead of decoding.
+    /// Returns whether the frame's output should be kept, as it's garbage without its bit reservoir data
+    fn prepare_frame(&mut self, next_frame: Option<&MP3FrameInfo>) -> Result<bool, EasyModeErr> {
+        if let Some(next_frame) = next_frame {
+            self.check_format_change(next_frame)?;
+        }
+        self.check_replaygain();
+        self.check_vbr_info();
+        self.check_trim();
+        if let Some(violation) = self.strict_violation() {
+            self.reject_frame()?;
+            return Err(EasyModeErr::SpecViolation(violation));
+        }
+        Ok(self.mp3.has_main_data(self.buffer.borrow_slice()) != Some(false))
+    }
+
+    /// Drop the frame at the start of the buffer without decoding it or counting it in the stream position.
+    /// Frames without a usable length are dropped by resyncing to the next frame after them
+    fn reject_frame(&mut self) -> Result<(), EasyModeErr> {
+        let size = Mp3::parse_frame_info(self.buffer.borrow_slice()).map_or(0, |frame| frame.size);
+        let size = size.max(0) as usize;
+        if size == 0 {
+            self.recover();
+        } else if size <= self.buffer.used() {
+            self.buffer.increment_start(size);
+            self.last_consumed += size;
         } else {
-            let frame = self.mp3.get_next_frame_info(self.buffer.borrow_slice())?;
-            Ok(frame)
+            // wait for the whole frame, so that it is dropped in one go
+            return Err(EasyModeErr::InDataUnderflow);
         }
+        Ok(())
     }
-}
 
-/// Errors that occur when calling the decode function
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
-pub enum EasyModeErr {
-    Okay,
-    InDataUnderflow,
-    MaindataUnderfow,
-    FreeBitrateSync,
-    OutOfMemory,
-    NullPointer,
-    InvalidFrameheader,
-    InvalidSideinfo,
-    InvalidScalefact,
-    InvalidHuffcodes,
-    InvalidDequantize,
-    InvalidImdct,
-    InvalidSubband,
-    Unknown,
-    InvalidError,
-    AudioBufferTooSmall,
-}
+    /// Decode the frame at the start of the buffer with `ThreePM`, timing it.
+    /// Returns how many bytes of the buffer are left over
+    fn decode_timed(
+        &mut self,
+        buffered_data_len: i32,
+        output_audio: &mut [i16],
+    ) -> Result<i32, DecodeErr> {
+        #[cfg(feature = "bench")]
+        let stopwatch = crate::cycles::Stopwatch::start();
+        let counter_start = self.cycle_counter.map(|counter| counter());
+        let result = self
+            .mp3
+            .decode(self.buffer.borrow_slice(), buffered_data_len, output_audio);
+        self.record_cycle_counter(counter_start);
+        #[cfg(feature = "bench")]
+        self.record_cycles(stopwatch.elapsed());
+        result
+    }
 
-impl From<DecodeErr> for EasyModeErr {
-    fn from(value: DecodeErr) -> Self {
-        match value {
-            DecodeErr::Okay => EasyModeErr::Okay,
-            DecodeErr::InDataUnderflow => EasyModeErr::InDataUnderflow,
-            DecodeErr::MaindataUnderfow => EasyModeErr::MaindataUnderfow,
-            DecodeErr::FreeBitrateSync => EasyModeErr::FreeBitrateSync,
-            DecodeErr::OutOfMemory => EasyModeErr::OutOfMemory,
-            DecodeErr::NullPointer => EasyModeErr::NullPointer,
-            DecodeErr::InvalidFrameheader => EasyModeErr::InvalidFrameheader,
-            DecodeErr::InvalidSideinfo => EasyModeErr::InvalidSideinfo,
-            DecodeErr::InvalidScalefact => EasyModeErr::InvalidScalefact,
-            DecodeErr::InvalidHuffcodes => EasyModeErr::InvalidHuffcodes,
-            DecodeErr::InvalidDequantize => EasyModeErr::InvalidDequantize,
-            DecodeErr::InvalidImdct => EasyModeErr::InvalidImdct,
-            DecodeErr::InvalidSubband => EasyModeErr::InvalidSubband,
-            DecodeErr::Unknown => EasyModeErr::Unknown,
-            DecodeErr::InvalidError => EasyModeErr::InvalidError,
+    /// Keep track of a frame that `ThreePM` decoded into `output_audio`, leaving `newlen` bytes in the
+    /// buffer, and apply gain and trimming to its output. Returns the number of samples output
+    fn finish_frame(
+        &mut self,
+        frame: MP3FrameInfo,
+        newlen: i32,
+        main_data: bool,
+        output_audio: &mut [i16],
+    ) -> usize {
+        self.have_decoded = true;
+        self.last_consumed += self.buffer.consume_remaining(newlen);
+        self.set_frame_info(frame);
+        self.notify_frame(&frame);
+        if main_data {
+            self.last_frame_decoded = true;
+            let samples = frame.outputSamps.max(0) as usize;
+            self.process_output(&frame, &mut output_audio[..samples])
+        } else {
+            self.drop_output(&frame)
         }
     }
+
+    /// Decode the next MP3 audio frame like [decode](Self::decode), also returning how long decoding took.
+    ///
+    /// The time is measured in CPU cycles on Cortex-M (using the DWT cycle counter, which you need to
+    /// enable) or in nanoseconds when using std.
+    #[cfg(feature = "bench")]
+    pub fn decode_measured(
+        &mut self,
+        output_audio: &mut [i16],
+    ) -> Result<(usize, u64), EasyModeErr> {
+        let samples = self.decode(output_audio)?;
+        Ok((samples, self.last_frame_cycles))
+    }
+
+    /// The longest time taken to decode a single frame so far in this stream.
+    /// Use this to learn the worst-case decode latency, eg to size a watchdog timeout
+    #[cfg(feature = "bench")]
+    pub fn max_frame_cycles(&self) -> u64 {
+        self.max_frame_cycles
+    }
+
+    #[cfg(feature = "bench")]
+    fn record_cycles(&mut self, cycles: u64) {
+        self.last_frame_cycles = cycles;
+        self.max_frame_cycles = core::cmp::max(self.max_frame_cycles, cycles);
+    }
+
+    /// Time each frame decode with `counter`, a monotonic counter such as the Cortex-M DWT cycle counter.
+    /// The counter is read either side of the call into `ThreePM`, and it may wrap. See
+    /// [cycles_last_decode](Self::cycles_last_decode).
+    ///
+    /// Unlike `decode_measured` this doesn't need the `bench` feature, and works
+    /// with any counter the target has
+    pub fn set_cycle_counter(&mut self, counter: fn() -> u32) {
+        self.cycle_counter = Some(counter);
+    }
+
+    /// Call `callback` with the info of every frame after it is decoded, eg to watch the bitrate of a VBR
+    /// live stream without polling [mp3_info](Self::mp3_info). It is called from inside
+    /// [decode](Self::decode) and [decode_unchecked](Self::decode_unchecked), so keep it short
+    pub fn on_frame(&mut self, callback: fn(&MP3FrameInfo)) {
+        self.frame_callback = Some(callback);
+    }
+
+    /// Pass a decoded frame's info to the [frame callback](Self::on_frame), if there is one
+    fn notify_frame(&self, frame: &MP3FrameInfo) {
+        if let Some(callback) = self.frame_callback {
+            callback(frame);
+        }
+    }
+
+    /// How many counts of the [cycle counter](Self::set_cycle_counter) the most recent frame took to decode.
+    /// This is 0 if no counter has been set
+    pub fn cycles_last_decode(&self) -> u32 {
+        self.cycles_last_decode
+    }
+
+    /// Record how long a decode took, from the cycle counter reading taken before it
+    fn record_cycle_counter(&mut self, start: Option<u32>) {
+        if let (Some(counter), Some(start)) = (self.cycle_counter, start) {
+            self.cycles_last_decode = counter().wrapping_sub(start);
+        }
+    }
+
+    /// Raw 2-bit mode extension (0-3) of the most recently parsed frame header. See [Mp3::mode_ext]
+    pub fn mode_ext(&self) -> u8 {
+        self.mp3.mode_ext()
+    }
+
+    /// Channel mode of the most recently parsed frame header. See [Mp3::channel_mode]
+    pub fn channel_mode(&self) -> ChannelMode {
+        self.mp3.channel_mode()
+    }
+
+    /// The most recently parsed frame header, with its fields decoded. See [Mp3::frame_header]
+    pub fn decoded_frame_header(&self) -> Option<DecodedHeader> {
+        self.mp3.frame_header()
+    }
+
+    /// Approximately seek forwards to `target_sample` (counted per channel from the start of the stream).
+    ///
+    /// This only makes sense for constant bitrate files: the number of bytes to skip is calculated
+    /// from the bitrate and sample rate of the last known frame. If no frame info is available yet, the
+    /// buffer is scanned for the next frame first.
+    /// Skipped data that is already buffered is discarded immediately, the rest is discarded as it is added.
+    /// Decoding is then resynced on the next sync word - keep adding data until [mp3_decode_ready](Self::mp3_decode_ready)
+    /// returns true before decoding again.
+    ///
+    /// Only data that hasn't been decoded yet can be skipped, so seeking backwards returns
+    /// [EasyModeErr::InvalidSeek]. To seek backwards, feed the decoder from an earlier point in the file.
+    ///
+    /// Returns the sample index that decoding will resume from, which is aligned to a frame boundary
+    pub fn seek_to_sample(&mut self, target_sample: u64) -> Result<u64, EasyModeErr> {
+        if target_sample < self.samples_decoded {
+            return Err(EasyModeErr::InvalidSeek);
+        }
+        if self.frame_info.is_none() {
+            let _ = self.skip_to_next_sync_word();
+        }
+        let frame = self.mp3_info()?;
+        let frame_samples = samples_per_channel(&frame) as u64;
+        if frame_samples == 0 || frame.samprate <= 0 {
+            return Err(EasyModeErr::InvalidFrameheader);
+        }
+        let frames = (target_sample - self.samples_decoded) / frame_samples;
+        let bytes =
+            (frames * frame_samples * frame.bitrate as u64 / (8 * frame.samprate as u64)) as usize;
+
+        // discard what we can from the buffer now, the rest gets skipped as it's added
+        let skipped = self.buffer_skip(bytes);
+        self.bytes_to_skip += bytes - skipped;
+        self.sync = false;
+        if self.bytes_to_skip == 0 {
+            let _ = self.skip_to_next_sync_word();
+        }
+        self.samples_decoded += frames * frame_samples;
+        self.samples_emitted = self.samples_decoded.saturating_sub(self.trim_start);
+        Ok(self.samples_decoded)
+    }
+
+    /// How many samples per channel have been output since the start of the stream.
+    ///
+    /// This counts what was actually returned by every decode method, after trimming and any mono
+    /// downmix, and keeps counting across loop restarts. After [seek_to_sample](Self::seek_to_sample)
+    /// it is the position that decoding resumes from
+    pub fn position_samples(&self) -> u64 {
+        self.samples_emitted
+    }
+
+    /// [position_samples](Self::position_samples) in microseconds, using the sample rate of the last frame decoded.
+    /// Returns 0 until a frame has been decoded
+    pub fn position_us(&self) -> u64 {
+        match self.frame_info {
+            Some(frame) if frame.samprate > 0 => {
+                self.samples_emitted * 1_000_000 / frame.samprate as u64
+            }
+            _ => 0,
+        }
+    }
+
+    /// Seek to `percent` (0 to 100) of the way through a track whose MP3 data is `total_bytes` long.
+    ///
+    /// VBR streams are seeked using the seek table in the Xing header of the first frame, so decode
+    /// at least one frame first. Streams without a seek table are assumed to be constant bitrate.
+    /// The buffer is emptied and the decoder's history is cleared, but the caller owns the actual file seek:
+    /// seek your data source to the returned byte offset and add data from there. The offset is counted from
+    /// the first MP3 frame, so add the length of any ID3v2 tag before it.
+    /// Decoding resyncs on the next sync word, and the first frames may be dropped while the bit reservoir refills
+    pub fn seek_to_percent(&mut self, percent: f32, total_bytes: u64) -> Result<u64, EasyModeErr> {
+        let frame = self.mp3_info()?;
+        let percent = percent.clamp(0.0, 100.0);
+        let vbr = self.vbr_info;
+        let offset = vbr
+            .and_then(|vbr| vbr.seek_byte_in(percent, total_bytes))
+            .unwrap_or((total_bytes as f32 * percent / 100.0) as u64);
+
+        // estimate where in the track we'll resume from
+        let frame_samples = samples_per_channel(&frame) as u64;
+        let total_samples = match vbr.and_then(|vbr| vbr.frames) {
+            Some(frames) => frames as u64 * frame_samples,
+            None if frame.bitrate > 0 => {
+                total_bytes * 8 * frame.samprate.max(0) as u64 / frame.bitrate as u64
+            }
+            None => 0,
+        };
+        let position = (total_samples as f32 * percent / 100.0) as u64;
+
+        self.mp3.reset();
+        let _ = self.buffer.take_slice();
+        self.sync = false;
+        self.parsed_id3 = true;
+        self.skipping_id3 = false;
+        self.bytes_to_skip = 0;
+        self.loop_restart = false;
+        self.samples_decoded = position;
+        self.samples_emitted = position.saturating_sub(self.trim_start);
+        Ok(offset)
+    }
+
+    /// Prime the decoder's bit reservoir and overlap state with `lookbehind`, the MP3 data just before the
+    /// point decoding is about to resume from, to reduce the glitch after a seek.
+    ///
+    /// Without this the first frames after a seek may refer to bit reservoir data that the decoder never
+    /// saw, so they are dropped, and the frame after that starts without the overlap from the one before it.
+    /// Every complete frame in `lookbehind` is decoded into the [scratch buffer](Self::with_scratch), which
+    /// takes 4.5KiB of stack if none is set, and thrown away without changing the stream buffer or position.
+    /// A frame's main data can start up to 511 bytes before it (255 bytes for MPEG-2 and MPEG-2.5), so
+    /// pass at least the 2 frames before the seek target, or 3 for low bitrate streams.
+    /// Call this after seeking, as seeking clears the decoder state.
+    /// Returns how many frames were decoded
+    pub fn warm_start(&mut self, lookbehind: &[u8]) -> usize {
+        let Some(scratch) = self.scratch.take() else {
+            return self.warm_start_stack_scratch(lookbehind);
+        };
+        let primed = self.prime_decoder(lookbehind, scratch);
+        self.scratch = Some(scratch);
+        primed
+    }
+
+    /// [warm_start](Self::warm_start) with a scratch buffer on the stack, not inlined like
+    /// [decode_stack_scratch](Self::decode_stack_scratch)
+    #[inline(never)]
+    fn warm_start_stack_scratch(&mut self, lookbehind: &[u8]) -> usize {
+        let mut scratch = [0i16; MAX_OUTPUT_SAMPLES];
+        self.prime_decoder(lookbehind, &mut scratch)
+    }
+
+    /// Decode every complete frame in `lookbehind` into `scratch` to prime the decoder's state.
+    /// Returns how many frames were decoded
+    fn prime_decoder(&mut self, lookbehind: &[u8], scratch: &mut [i16]) -> usize {
+        let mut primed = 0;
+        for frame in Mp3::scan_frames(lookbehind) {
+            let end = frame.byte_offset + frame.size;
+            let Some(data) = lookbehind.get(frame.byte_offset..end) else {
+                break;
+            };
+            // the first frames are expected to lack main data, what they carry is kept for the next
+            match self.mp3.decode_frame(data, scratch) {
+                Ok(_) | Err(DecodeErr::MaindataUnderfow) => primed += 1,
+                Err(_) => {}
+            }
+        }
+        primed
+    }
+
+    /// Loop points read from the stream's ID3v2 tag as `(start, end)`, in samples per channel.
+    ///
+    /// These are read from the TXXX frames `LOOPSTART` and either `LOOPEND` or `LOOPLENGTH`,
+    /// as used by game engines for seamlessly looping music. They are only found if the tag is skipped
+    /// by [mp3_decode_ready](Self::mp3_decode_ready) rather than being removed from the data beforehand
+    pub fn loop_points(&self) -> Option<(u64, u64)> {
+        let start = self.loop_start?;
+        let end = self.loop_end.or(self.loop_length.map(|len| start + len))?;
+        (end > start).then_some((start, end))
+    }
+
+    /// Enable or disable looping between the stream's [loop_points](Self::loop_points).
+    ///
+    /// When decoding reaches the end loop point, the output stops exactly at that sample and the next decode
+    /// returns [EasyModeErr::LoopRestart]. The stream buffer is cleared at that point: feed the stream again
+    /// from the start of the file, and wait for [mp3_decode_ready](Self::mp3_decode_ready) as you would for a
+    /// new stream. Frames before the start loop point are decoded to warm up the decoder's state, but no samples
+    /// are output for them, so expect some decode calls to return 0 samples.
+    /// Looping has no effect on streams without loop points
+    pub fn set_loop(&mut self, enabled: bool) {
+        self.looping = enabled;
+    }
+
+    /// If we reached the end loop point, reset the stream so it can be fed again from the start
+    fn check_loop_restart(&mut self) -> Result<(), EasyModeErr> {
+        if !self.loop_restart {
+            return Ok(());
+        }
+        self.loop_restart = false;
+        let _ = self.buffer.take_slice();
+        self.sync = false;
+        self.parsed_id3 = false;
+        self.bytes_to_skip = 0;
+        self.samples_decoded = 0;
+        self.discard_until = self.loop_start.unwrap_or(0);
+        Err(EasyModeErr::LoopRestart)
+    }
+
+    /// Look for loop point tags in the next `len` bytes of ID3v2 tag in the buffer
+    fn find_loop_tags(&mut self, len: usize) {
+        let tag = &self.buffer.borrow_slice()[..len];
+        self.loop_start = self.loop_start.or(Mp3::find_txxx_number(tag, b"LOOPSTART"));
+        self.loop_end = self.loop_end.or(Mp3::find_txxx_number(tag, b"LOOPEND"));
+        self.loop_length = self
+            .loop_length
+            .or(Mp3::find_txxx_number(tag, b"LOOPLENGTH"));
+    }
+
+    /// Normalise the loudness of decoded audio using the ReplayGain stored in the stream's LAME tag.
+    ///
+    /// The LAME tag is only present in the first frame of a stream, so call this before the first decode.
+    /// If the requested gain isn't in the tag the other one is used. Streams without a LAME tag are unchanged.
+    /// Samples are clipped to the i16 limits when the gain is positive, see [clip_count](Self::clip_count)
+    pub fn enable_replaygain(&mut self, mode: ReplayGainMode) {
+        self.replaygain = Some(mode);
+        self.replaygain_checked = false;
+    }
+
+    /// Trim `delay` samples from the start of the stream and `padding` samples from the end, for gapless
+    /// playback. Both are counted in samples per channel, and cleared by [reset](Self::reset).
+    ///
+    /// Use the values in [VbrInfo], fetched from the stream's first frame with
+    /// [Mp3::parse_vbr_header]. The decoder itself delays its output by 529 samples, so pass
+    /// `encoder_delay + 529` and `encoder_padding - 529` to trim exactly the silence the encoder added.
+    /// Call this before the first decode: if the first frame has a Xing/Info header, it is trimmed too and the
+    /// frame count in the header is used to find the end. Without a Xing/Info header only `delay` is trimmed
+    pub fn set_trim(&mut self, delay: u32, padding: u32) {
+        self.trim = Some((delay, padding));
+        self.trim_checked = false;
+    }
+
+    /// Automatically trim the encoder delay and padding recorded in the stream's LAME tag, for gapless playback.
+    /// This is off by default. The decoder's own delay is included, so only the original audio is output.
+    /// Streams without a LAME tag are untrimmed, and [set_trim](Self::set_trim) takes priority over this
+    pub fn set_trim_encoder_delay(&mut self, trim: bool) {
+        self.trim_encoder_delay = trim;
+    }
+
+    /// Set a separate gain for the left and right channels, eg for a balance control.
+    ///
+    /// Gains are in Q8 fixed point, so 256 is unity gain. They multiply with any other gain being applied.
+    /// Mono frames only have the left gain applied.
+    pub fn set_channel_gain(&mut self, left_q8: u16, right_q8: u16) {
+        self.channel_gain_q8 = (left_q8, right_q8);
+    }
+
+    /// Also count decoded samples that are already at full scale (`i16::MAX` or `i16::MIN`) in
+    /// [clip_count](Self::clip_count), to flag streams that were clipped before they were encoded,
+    /// and measure [last_frame_peaks](Self::last_frame_peaks).
+    /// This is off by default, as it means checking every sample that is output
+    pub fn set_track_clipping(&mut self, track: bool) {
+        self.track_clipping = track;
+    }
+
+    /// Peak absolute sample value of the left and right channels output by the most recent decode, eg for a
+    /// VU meter. Both are the same for mono frames. This is only measured when
+    /// [set_track_clipping](Self::set_track_clipping) is enabled, otherwise it is always `(0, 0)`
+    pub fn last_frame_peaks(&self) -> (i16, i16) {
+        self.last_frame_peaks
+    }
+
+    /// How many decoded samples have been clipped by the gain stage, plus the samples output at full
+    /// scale if [set_track_clipping](Self::set_track_clipping) is enabled
+    pub fn clip_count(&self) -> u32 {
+        self.clip_count
+    }
+
+    /// If ReplayGain was requested, look for a LAME tag in the frame we're about to decode
+    fn check_replaygain(&mut self) {
+        if let (Some(mode), false) = (self.replaygain, self.replaygain_checked) {
+            self.replaygain_checked = true;
+            if let Some(replaygain) = Mp3::parse_replaygain(self.buffer.borrow_slice()) {
+                let db_tenths = match mode {
+                    ReplayGainMode::Track => replaygain.track.or(replaygain.album),
+                    ReplayGainMode::Album => replaygain.album.or(replaygain.track),
+                };
+                if let Some(db_tenths) = db_tenths {
+                    self.gain_q16 = gain::db_tenths_to_q16(db_tenths);
+                }
+            }
+        }
+    }
+
+    /// Keep the Xing/Info header of the first frame, for seeking
+    fn check_vbr_info(&mut self) {
+        if !self.vbr_checked {
+            self.vbr_checked = true;
+            self.vbr_info = Mp3::parse_vbr_header(self.buffer.borrow_slice());
+        }
+    }
+
+    /// Work out which samples to trim once trimming is enabled, while the first frame is still in the buffer
+    fn check_trim(&mut self) {
+        if self.trim_checked || (self.trim.is_none() && !self.trim_encoder_delay) {
+            return;
+        }
+        self.trim_checked = true;
+        let data = self.buffer.borrow_slice();
+        let frame_samples = Mp3::parse_frame_info(data)
+            .map(|frame| samples_per_channel(&frame) as u64)
+            .unwrap_or(0);
+        let vbr = Mp3::parse_vbr_header(data);
+        if self.trim.is_none() {
+            // only a LAME tag records the encoder delay and padding
+            match &vbr {
+                Some(vbr) if vbr.encoder_delay != 0 || vbr.encoder_padding != 0 => {
+                    self.trim = Some((
+                        vbr.encoder_delay as u32 + DECODER_DELAY,
+                        (vbr.encoder_padding as u32).saturating_sub(DECODER_DELAY),
+                    ));
+                }
+                _ => return,
+            }
+        }
+        let Some((delay, padding)) = self.trim else {
+            return;
+        };
+        if let Some(vbr) = vbr {
+            // the Xing/Info frame decodes to silence, and isn't included in its frame count
+            self.trim_start = frame_samples + delay as u64;
+            self.trim_end = vbr
+                .frames
+                .map(|frames| ((frames as u64 + 1) * frame_samples).saturating_sub(padding as u64));
+        } else {
+            self.trim_start = delay as u64;
+            self.trim_end = None;
+        }
+    }
+
+    /// Apply any post-processing to a freshly decoded frame.
+    /// Returns how many of the decoded samples should be output, which are moved to the start of `output_audio`
+    fn process_output(&mut self, frame: &MP3FrameInfo, output_audio: &mut [i16]) -> usize {
+        let channels = frame.nChans.max(1) as usize;
+        let frame_samples = samples_per_channel(frame) as u64;
+        let (keep_start, keep_end, loop_end_reached) = self.output_range(frame_samples);
+        self.samples_decoded += frame_samples;
+        self.loop_restart |= loop_end_reached;
+        self.samples_emitted += keep_end - keep_start;
+        let keep = (keep_start as usize * channels)..(keep_end as usize * channels);
+        let output_audio = if keep.start > 0 {
+            let len = keep.len();
+            output_audio.copy_within(keep, 0);
+            &mut output_audio[..len]
+        } else {
+            &mut output_audio[..keep.end]
+        };
+
+        let (left_q8, right_q8) = self.channel_gain_q8;
+        let gain_q16 = gain::scale_q16_by_q8(self.gain_q16, left_q8);
+        let clipped = if frame.nChans == 2 && (left_q8, right_q8) != (256, 256) {
+            let right_q16 = gain::scale_q16_by_q8(self.gain_q16, right_q8);
+            gain::apply_stereo(output_audio, gain_q16, right_q16)
+        } else if gain_q16 != gain::UNITY_Q16 {
+            gain::apply(output_audio, gain_q16)
+        } else {
+            0
+        };
+        // samples clipped by the gain stage end up at full scale, so they're counted either way
+        self.clip_count += if self.track_clipping {
+            self.last_frame_peaks = gain::peaks(output_audio, channels);
+            gain::count_full_scale(output_audio)
+        } else {
+            clipped
+        };
+        output_audio.len()
+    }
+
+    /// Work out which samples of the next frame, which has `frame_samples` samples per channel, should be
+    /// output once anything outside of the loop, seek and trim points is trimmed off.
+    /// Returns the start and end sample to keep within the frame, and whether the loop end was reached
+    fn output_range(&self, frame_samples: u64) -> (u64, u64, bool) {
+        let frame_start = self.samples_decoded;
+        let mut keep_end = frame_samples;
+        let mut loop_end_reached = false;
+        if let (true, Some((_, loop_end))) = (self.looping, self.loop_points()) {
+            if frame_start + frame_samples >= loop_end {
+                keep_end = loop_end.saturating_sub(frame_start).min(frame_samples);
+                loop_end_reached = true;
+            }
+        }
+        if let Some(trim_end) = self.trim_end {
+            keep_end = keep_end.min(trim_end.saturating_sub(frame_start));
+        }
+        let keep_start = self
+            .discard_until
+            .max(self.trim_start)
+            .saturating_sub(frame_start)
+            .min(keep_end);
+        (keep_start, keep_end, loop_end_reached)
+    }
+
+    /// Get the frame at the start of the buffer ready to decode, without decoding it, eg to check that
+    /// two streams line up before decoding either of them. Returns the frame's info and how many samples
+    /// decoding it would output, which is 0 if the frame would be dropped
+    fn peek_decode(&mut self) -> Result<(MP3FrameInfo, usize), EasyModeErr> {
+        self.start_decode()?;
+        let next_frame = self.next_frame_info()?;
+        self.check_format_change(&next_frame)?;
+        self.check_trim();
+        if next_frame.size.max(0) as usize > self.buffer.used() {
+            return Err(EasyModeErr::InDataUnderflow);
+        }
+        if self.strict_violation().is_some()
+            || self.mp3.has_main_data(self.buffer.borrow_slice()) == Some(false)
+        {
+            return Ok((next_frame, 0));
+        }
+        let (keep_start, keep_end, _) = self.output_range(samples_per_channel(&next_frame) as u64);
+        let channels = next_frame.nChans.max(1) as usize;
+        Ok((next_frame, (keep_end - keep_start) as usize * channels))
+    }
+
+    /// Drop the output of a decoded frame, keeping track of the stream position. Always returns 0 samples
+    fn drop_output(&mut self, frame: &MP3FrameInfo) -> usize {
+        self.samples_decoded += samples_per_channel(frame) as u64;
+        self.last_frame_peaks = (0, 0);
+        0
+    }
+
+    /// Skip a frame that `ThreePM` refused to decode because its bit reservoir data is missing.
+    /// The decoder still keeps the frame's main data for the frames after it. Always returns 0 samples
+    fn drop_frame(&mut self, frame: MP3FrameInfo) -> usize {
+        let size = (frame.size.max(0) as usize).min(self.buffer.used());
+        self.buffer.increment_start(size);
+        self.last_consumed += size;
+        self.have_decoded = true;
+        self.set_frame_info(frame);
+        self.drop_output(&frame)
+    }
+
+    /// The largest number of samples any frame in this stream has decoded to so far.
+    ///
+    /// Streams can switch between mono and stereo, so after priming a few frames this tells you
+    /// the output buffer size this particular stream actually needs
+    pub fn max_output_samples_seen(&self) -> usize {
+        self.max_output_samples
+    }
+
+    fn set_frame_info(&mut self, frame: MP3FrameInfo) {
+        self.max_output_samples =
+            core::cmp::max(self.max_output_samples, frame.outputSamps as usize);
+        self.frame_info = Some(frame);
+    }
+
+    /// Parse the header of the frame at the start of the buffer
+    fn next_frame_info(&mut self) -> Result<MP3FrameInfo, EasyModeErr> {
+        self.mp3
+            .get_next_frame_info(self.buffer.borrow_slice())
+            .map_err(|e| self.frame_err(e))
+    }
+
+    /// Reject frames that deviate from the MPEG audio spec instead of decoding them on a best-effort basis.
+    /// This is off by default, so that playback is as tolerant as possible.
+    ///
+    /// When enabled, decoding a frame returns [EasyModeErr::SpecViolation] if the frame:
+    /// - uses a reserved bitrate, sample rate or emphasis value
+    /// - has a CRC that doesn't match its header and side info
+    /// - has a frame length that doesn't lead to the next frame header (or ID3v1 tag). This is only checked
+    ///   when the next frame header is already in the buffer
+    ///
+    /// The frame is dropped from the buffer without being decoded once all of it has arrived, so decoding
+    /// can continue with the next frame. Dropped frames don't count towards the
+    /// [stream position](Self::position_samples), and the frame after one may fail to decode if it uses
+    /// its bit reservoir
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Return [EasyModeErr::FormatChanged] from decoding when the sample rate or channel count of the next
+    /// frame differs from the frame before it, eg in concatenated MP3s, so that the output can be reconfigured.
+    /// This is off by default, and the new format is decoded as normal.
+    ///
+    /// The frame isn't decoded when the error is returned, and [mp3_info](Self::mp3_info) reports its format.
+    /// Decoding again acknowledges the change and decodes the frame
+    pub fn set_report_format_changes(&mut self, report: bool) {
+        self.report_format_changes = report;
+    }
+
+    /// Keep track of the format of the frames being decoded, reporting a change if that is enabled
+    fn check_format_change(&mut self, next_frame: &MP3FrameInfo) -> Result<(), EasyModeErr> {
+        let format = (next_frame.samprate, next_frame.nChans);
+        let previous = self.output_format.replace(format);
+        if self.report_format_changes && matches!(previous, Some(previous) if previous != format) {
+            self.set_frame_info(*next_frame);
+            return Err(EasyModeErr::FormatChanged);
+        }
+        Ok(())
+    }
+
+    /// Decode the next frame like [decode](Self::decode) while `remaining_frames` is above zero, counting
+    /// it down for each frame decoded, eg to generate a preview from the first few seconds of a track.
+    ///
+    /// Frames that are dropped rather than decoded, eg for a bad CRC or missing bit reservoir data,
+    /// don't count. Once it reaches zero nothing more is decoded and `Ok(0)` is returned, so check
+    /// `remaining_frames` to tell this apart from a dropped frame. The decoder is left as it was, ready
+    /// for more decoding or a [reset](Self::reset)
+    pub fn decode_limited(
+        &mut self,
+        output_audio: &mut [i16],
+        remaining_frames: &mut u32,
+    ) -> Result<usize, EasyModeErr> {
+        if *remaining_frames == 0 {
+            return Ok(0);
+        }
+        let samples = self.decode(output_audio)?;
+        if self.last_frame_decoded {
+            *remaining_frames -= 1;
+        }
+        Ok(samples)
+    }
+
+    /// Decode what's left in the MP3 stream buffer at the end of the stream, once there's no more data
+    /// to add. Call this in place of [decode](Self::decode) until it returns `Ok(0)`.
+    ///
+    /// Each call decodes one of the remaining complete frames. Frames that can't be decoded are
+    /// skipped, as is a trailing partial frame, which [decode](Self::decode) would keep reporting as
+    /// [EasyModeErr::InDataUnderflow]. Once no complete frames are left the buffer is emptied and
+    /// `Ok(0)` is returned, meaning the stream is done, rather than that more data is needed.
+    /// Free format frames can't be split out of the buffer without decoding, so they aren't flushed.
+    /// Errors that the caller needs to act on, such as [EasyModeErr::AudioBufferTooSmall], are
+    /// still returned
+    pub fn finish(&mut self, output_audio: &mut [i16]) -> Result<usize, EasyModeErr> {
+        loop {
+            self.skip_to_next_sync_word();
+            if self.complete_frames_buffered() == 0 {
+                self.buffer_clear();
+                return Ok(0);
+            }
+            match self.decode(output_audio) {
+                // the frame's output was dropped, eg while the bit reservoir refills
+                Ok(0) => {}
+                Ok(samples) => return Ok(samples),
+                Err(
+                    e @ (EasyModeErr::AudioBufferTooSmall
+                    | EasyModeErr::LoopRestart
+                    | EasyModeErr::FormatChanged
+                    | EasyModeErr::SpecViolation(_)),
+                ) => return Err(e),
+                Err(_) => {
+                    self.recover();
+                }
+            }
+        }
+    }
+
+    /// Decode the next frame like [decode](Self::decode), checking its CRC first so that errors say whether
+    /// the frame was damaged in transit.
+    ///
+    /// A frame whose CRC doesn't match isn't decoded, and [EasyModeErr::SpecViolation] is returned with
+    /// `crc_ok` set to `Some(false)`. The frame is left in the buffer, so it can be skipped with
+    /// [recover](Self::recover) or decoded anyway with [decode](Self::decode)
+    pub fn decode_checked(&mut self, output_audio: &mut [i16]) -> Result<usize, CheckedDecodeErr> {
+        let crc_ok = Mp3::verify_frame_crc(self.buffer.borrow_slice());
+        if crc_ok == Some(false) {
+            let err = EasyModeErr::SpecViolation(SpecViolation::CrcMismatch);
+            return Err(CheckedDecodeErr { err, crc_ok });
+        }
+        self.decode(output_audio).map_err(|err| CheckedDecodeErr { err, crc_ok })
+    }
+
+    /// Skip frames whose CRC doesn't match instead of decoding them, eg for streams received over an unreliable link.
+    /// This is off by default. Only frames protected by a CRC can be checked, and a frame is only skipped once
+    /// it is completely in the buffer. Frames after a skipped one may fail to decode if they use its bit reservoir.
+    /// See [bad_crc_frames](Self::bad_crc_frames) for how many frames have been skipped
+    pub fn set_skip_bad_crc(&mut self, skip: bool) {
+        self.skip_bad_crc = skip;
+    }
+
+    /// How many frames have been skipped because their CRC didn't match, see [set_skip_bad_crc](Self::set_skip_bad_crc)
+    pub fn bad_crc_frames(&self) -> u32 {
+        self.bad_crc_frames
+    }
+
+    /// Drop any complete frames with a bad CRC from the start of the buffer if CRC skipping is enabled
+    fn skip_bad_crc_frames(&mut self) {
+        while self.skip_bad_crc {
+            let data = self.buffer.borrow_slice();
+            if Mp3::verify_frame_crc(data) != Some(false) {
+                break;
+            }
+            match Mp3::parse_frame_info(data) {
+                Some(frame) if frame.size > 0 && frame.size as usize <= data.len() => {
+                    self.buffer.increment_start(frame.size as usize);
+                    self.last_consumed += frame.size as usize;
+                    // keep the stream position in step, as if the frame had been decoded
+                    self.samples_decoded += samples_per_channel(&frame) as u64;
+                    self.bad_crc_frames += 1;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// How much MP3 data is in the buffer, as the length type `ThreePM` uses
+    fn buffered_len(&self) -> Result<i32, EasyModeErr> {
+        self.buffer.used_c_int().ok_or(EasyModeErr::BufferTooLarge)
+    }
+
+    /// Check the frame at the start of the buffer if strict mode is enabled
+    fn strict_violation(&self) -> Option<SpecViolation> {
+        if !self.strict {
+            return None;
+        }
+        Mp3::check_conformance(self.buffer.borrow_slice()).err()
+    }
+
+    /// Work out why the frame at the start of the buffer could not be decoded.
+    /// ThreePM rejects the headers of frames that aren't Layer 3, so report those as unsupported
+    fn frame_err(&self, e: DecodeErr) -> EasyModeErr {
+        frame_err(self.buffer.borrow_slice(), e)
+    }
+
+    /// Get MP3 metadata from the last MP3 frame decoded, or the frame at the start of the buffer.
+    /// Returns [EasyModeErr::NotReady] if the buffer hasn't been synced to a frame header yet, add more
+    /// data and call it again. Other errors mean the frame header there is invalid
+    pub fn mp3_info(&mut self) -> Result<MP3FrameInfo, EasyModeErr> {
+        if let Some(frameinfo) = self.frame_info {
+            Ok(frameinfo)
+        } else if !self.sync || self.buffer.used() < 4 {
+            Err(EasyModeErr::NotReady)
+        } else {
+            let frame = self.mp3.get_next_frame_info(self.buffer.borrow_slice())?;
+            Ok(frame)
+        }
+    }
+
+    /// Sample rate of the stream in Hz, from the last frame decoded or synced to.
+    /// Returns None before the first frame is known
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.frame_info.map(|frame| frame.samprate.max(0) as u32)
+    }
+
+    /// Number of channels in the stream, from the last frame decoded or synced to.
+    /// Returns None before the first frame is known
+    pub fn channels(&self) -> Option<u8> {
+        self.frame_info.map(|frame| frame.nChans.max(0) as u8)
+    }
+
+    /// Whether the stream is stereo, from the last frame decoded or synced to.
+    /// Returns None before the first frame is known
+    pub fn is_stereo(&self) -> Option<bool> {
+        self.channels().map(|channels| channels == 2)
+    }
+
+    /// How many samples the output buffer passed to [decode](Self::decode) must hold to decode the next frame.
+    ///
+    /// Call this before the first decode to allocate or check the output buffer, instead of finding out
+    /// with [EasyModeErr::AudioBufferTooSmall]. Streams can switch between mono and stereo, so use
+    /// [MAX_OUTPUT_SAMPLES] if you need a buffer that fits every frame
+    pub fn required_output_len(&mut self) -> Result<usize, EasyModeErr> {
+        let next_frame = self.next_frame_info()?;
+        Ok(next_frame.outputSamps.max(0) as usize)
+    }
+
+    /// How many samples the output buffer must hold to decode the next frame in the buffer, like
+    /// [required_output_len](Self::required_output_len) but without changing any decoder state.
+    /// The header is parsed in Rust, see [peek_next_frame_info](Self::peek_next_frame_info)
+    pub fn next_frame_output_len(&self) -> Result<usize, EasyModeErr> {
+        let next_frame = self.peek_next_frame_info()?;
+        Ok(next_frame.outputSamps.max(0) as usize)
+    }
+
+    /// How many complete frames are in the MP3 stream buffer, eg to decode a batch of frames before topping
+    /// the buffer up again. Frames are found by walking the frame headers from the start of the buffer,
+    /// see [Mp3::scan_frames], so free format frames aren't counted
+    pub fn complete_frames_buffered(&self) -> usize {
+        let data = self.buffer.borrow_slice();
+        Mp3::scan_frames(data)
+            .take_while(|frame| frame.byte_offset + frame.size <= data.len())
+            .count()
+    }
+
+    /// Work out whether the stream is constant or variable bitrate from up to `max_frames` frame headers
+    /// in the buffer, without decoding any audio. See [Mp3::detect_bitrate_mode] for details.
+    ///
+    /// Call this once the buffer is synced to the first frame, so its Xing/Info header can be used
+    pub fn detect_bitrate_mode(&self, max_frames: usize) -> BitrateMode {
+        Mp3::detect_bitrate_mode(self.buffer.borrow_slice(), max_frames)
+    }
+
+    /// Get MP3 metadata for the next frame in the buffer without decoding it or changing any decoder state,
+    /// for example to react to a sample rate change before it is decoded.
+    ///
+    /// This parses the frame header in Rust, as the `ThreePM` function updates the decoder while parsing
+    pub fn peek_next_frame_info(&self) -> Result<MP3FrameInfo, EasyModeErr> {
+        let data = self.buffer.borrow_slice();
+        match Mp3::parse_frame_info(data) {
+            Some(frame) if frame.layer == 3 => Ok(frame),
+            Some(_) => Err(self.frame_err(DecodeErr::InvalidFrameheader)),
+            None if data.len() < 4 => Err(EasyModeErr::InDataUnderflow),
+            None => Err(EasyModeErr::InvalidFrameheader),
+        }
+    }
+
+    /// Decode an entire in-memory MP3 into a `Vec`, returning all of the decoded samples.
+    ///
+    /// Handles ID3 skipping and syncing before decoding. Running out of input ends decoding cleanly,
+    /// including a trailing partial frame. See [decode_all_capped](Self::decode_all_capped) to limit
+    /// how many samples are decoded
+    #[cfg(feature = "alloc")]
+    pub fn decode_all(&mut self, data: &[u8]) -> Result<Vec<i16>, EasyModeErr> {
+        self.decode_all_capped(data, usize::MAX)
+    }
+
+    /// Decode an entire in-memory MP3, stopping once `max_samples` samples have been produced.
+    ///
+    /// Handles ID3 skipping and syncing before decoding. When the cap is hit the output is
+    /// truncated to exactly `max_samples` and returned as `Ok` - it is not treated as an error.
+    /// Running out of input also ends decoding cleanly, including a trailing partial frame.
+    /// Frames that can't be decoded are skipped like [recover](Self::recover) does, so a corrupt
+    /// frame part way through doesn't lose the samples decoded before it
+    #[cfg(feature = "alloc")]
+    pub fn decode_all_capped(
+        &mut self,
+        data: &[u8],
+        max_samples: usize,
+    ) -> Result<Vec<i16>, EasyModeErr> {
+        let mut output = Vec::new();
+        let mut frame = [0i16; MAX_OUTPUT_SAMPLES];
+        let mut remaining = data;
+
+        // skip past the id3 tags and anything else up to the first mp3 sync tag
+        loop {
+            let added = self.add_data(remaining);
+            remaining = &remaining[added..];
+            let used = self.buffer_used();
+            if self.mp3_decode_ready() {
+                break;
+            }
+            if remaining.is_empty() && added == 0 && self.buffer_used() == used {
+                // there was nothing in the input that we could decode
+                return Ok(output);
+            }
+        }
+
+        while output.len() < max_samples {
+            let added = self.add_data(remaining);
+            remaining = &remaining[added..];
+            let result = if remaining.is_empty() {
+                // all of the input is buffered, so flush the frames that are left
+                match self.finish(&mut frame) {
+                    Ok(0) => break,
+                    result => result,
+                }
+            } else {
+                self.decode(&mut frame)
+            };
+            match result {
+                Ok(samples) => {
+                    let take = core::cmp::min(samples, max_samples - output.len());
+                    output.extend_from_slice(&frame[..take]);
+                }
+                Err(EasyModeErr::InDataUnderflow) if added > 0 => {
+                    // we topped up the buffer this time round, try again with the new data
+                }
+                // the bad frame has already been dropped, or the new format is decoded next time
+                Err(EasyModeErr::SpecViolation(_) | EasyModeErr::FormatChanged) => {}
+                Err(EasyModeErr::LoopRestart) => break,
+                Err(e @ (EasyModeErr::AudioBufferTooSmall | EasyModeErr::BufferTooLarge)) => {
+                    return Err(e)
+                }
+                // skip past a frame that can't be decoded, eg a corrupt one, and carry on after it
+                Err(_) => {
+                    self.recover();
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+impl<const N: usize> Default for EasyModeN<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builder for an [EasyMode] decoder, using the default 1024 byte MP3 stream buffer
+pub type EasyModeBuilder = EasyModeBuilderN<1024>;
+
+/// Builder for an [EasyModeN] decoder with an `N` byte MP3 stream buffer, for opting into features up front.
+///
+/// ```rust
+/// use threepm::easy_mode::EasyModeBuilder;
+///
+/// let mut easy = EasyModeBuilder::new()
+///     .auto_skip_corrupt(true)
+///     .trim_encoder_delay(true)
+///     .buffer_size::<4096>()
+///     .build();
+/// assert_eq!(easy.buffer_free(), 4096);
+/// ```
+pub struct EasyModeBuilderN<const N: usize> {
+    auto_skip_corrupt: bool,
+    trim_encoder_delay: bool,
+    strict: bool,
+    compact_ratio: u8,
+    track_clipping: bool,
+    source: Option<fn(&mut [u8]) -> usize>,
+    cycle_counter: Option<fn() -> u32>,
+    frame_callback: Option<fn(&MP3FrameInfo)>,
+    report_format_changes: bool,
+}
+
+impl<const N: usize> EasyModeBuilderN<N> {
+    /// Construct a builder with every option off, which builds the same decoder as [EasyModeN::new]
+    pub const fn new() -> Self {
+        EasyModeBuilderN {
+            auto_skip_corrupt: false,
+            trim_encoder_delay: false,
+            strict: false,
+            compact_ratio: 0,
+            track_clipping: false,
+            source: None,
+            cycle_counter: None,
+            frame_callback: None,
+            report_format_changes: false,
+        }
+    }
+
+    /// Skip frames with a bad CRC, see [EasyModeN::set_skip_bad_crc]
+    pub const fn auto_skip_corrupt(mut self, skip: bool) -> Self {
+        self.auto_skip_corrupt = skip;
+        self
+    }
+
+    /// Trim the encoder delay and padding from the LAME tag, see [EasyModeN::set_trim_encoder_delay]
+    pub const fn trim_encoder_delay(mut self, trim: bool) -> Self {
+        self.trim_encoder_delay = trim;
+        self
+    }
+
+    /// Report frames that break the MP3 spec, see [EasyModeN::set_strict]
+    pub const fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Reduce how often the MP3 stream buffer is shuffled, see [EasyModeN::set_compact_ratio]
+    pub const fn compact_ratio(mut self, percent: u8) -> Self {
+        self.compact_ratio = percent;
+        self
+    }
+
+    /// Count samples output at full scale as clipped, see [EasyModeN::set_track_clipping]
+    pub const fn track_clipping(mut self, track: bool) -> Self {
+        self.track_clipping = track;
+        self
+    }
+
+    /// Top up the MP3 stream buffer from `source` when decoding, see [EasyModeN::set_source]
+    pub const fn source(mut self, source: fn(&mut [u8]) -> usize) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Report changes to the stream's format, see [EasyModeN::set_report_format_changes]
+    pub const fn report_format_changes(mut self, report: bool) -> Self {
+        self.report_format_changes = report;
+        self
+    }
+
+    /// Time each frame decode with `counter`, see [EasyModeN::set_cycle_counter]
+    pub const fn cycle_counter(mut self, counter: fn() -> u32) -> Self {
+        self.cycle_counter = Some(counter);
+        self
+    }
+
+    /// Call `callback` with the info of every decoded frame, see [EasyModeN::on_frame]
+    pub const fn on_frame(mut self, callback: fn(&MP3FrameInfo)) -> Self {
+        self.frame_callback = Some(callback);
+        self
+    }
+
+    /// Use an `M` byte MP3 stream buffer instead, keeping the options set so far
+    pub const fn buffer_size<const M: usize>(self) -> EasyModeBuilderN<M> {
+        EasyModeBuilderN {
+            auto_skip_corrupt: self.auto_skip_corrupt,
+            trim_encoder_delay: self.trim_encoder_delay,
+            strict: self.strict,
+            compact_ratio: self.compact_ratio,
+            track_clipping: self.track_clipping,
+            source: self.source,
+            cycle_counter: self.cycle_counter,
+            frame_callback: self.frame_callback,
+            report_format_changes: self.report_format_changes,
+        }
+    }
+
+    /// Construct the configured decoder
+    pub fn build(self) -> EasyModeN<N> {
+        let mut easy = EasyModeN::new();
+        easy.set_skip_bad_crc(self.auto_skip_corrupt);
+        easy.set_trim_encoder_delay(self.trim_encoder_delay);
+        easy.set_strict(self.strict);
+        easy.set_compact_ratio(self.compact_ratio);
+        easy.set_track_clipping(self.track_clipping);
+        easy.source = self.source;
+        easy.cycle_counter = self.cycle_counter;
+        easy.frame_callback = self.frame_callback;
+        easy.set_report_format_changes(self.report_format_changes);
+        easy
+    }
+}
+
+impl<const N: usize> Default for EasyModeBuilderN<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Iterator that decodes one frame per call to `next()`, created by [EasyModeN::frames].
+///
+/// Each item is the number of samples decoded into the output buffer, which can be read with
+/// [output](Self::output). If a data source was supplied with [source](Self::source) the MP3 stream
+/// buffer is topped up from it as needed, otherwise only the data already in the buffer is decoded.
+/// Iteration ends when there is no more data to decode, or after the first error.
+///
+/// ```rust
+/// use threepm::easy_mode::EasyMode;
+/// use threepm::mp3::MAX_OUTPUT_SAMPLES;
+///
+/// static MP3: &[u8] = &[0u8; 512];
+///
+/// let mut easy = EasyMode::new();
+/// let mut buf = [0i16; MAX_OUTPUT_SAMPLES];
+/// let mut frames = easy.frames(&mut buf).source(MP3.chunks(512));
+/// while let Some(Ok(_samples)) = frames.next() {
+///     let _audio = frames.output();
+///     // Do something with the decoded audio (like play or store it)
+/// }
+/// ```
+pub struct Frames<'a, const N: usize, S = core::iter::Empty<&'a [u8]>> {
+    easy: &'a mut EasyModeN<N>,
+    output: &'a mut [i16],
+    source: S,
+    pending: &'a [u8],
+    samples: usize,
+    eof: bool,
+    done: bool,
+}
+
+impl<'a, const N: usize, S: Iterator<Item = &'a [u8]>> Frames<'a, N, S> {
+    /// Top up the MP3 stream buffer from `source` whenever there is space for more data
+    pub fn source<T: Iterator<Item = &'a [u8]>>(self, source: T) -> Frames<'a, N, T> {
+        Frames {
+            easy: self.easy,
+            output: self.output,
+            source,
+            pending: &[],
+            samples: 0,
+            eof: false,
+            done: self.done,
+        }
+    }
+
+    /// The samples decoded by the last call to `next()`
+    pub fn output(&self) -> &[i16] {
+        &self.output[..self.samples]
+    }
+
+    /// Access the underlying EasyMode decoder, eg to query [EasyModeN::mp3_info]
+    pub fn easy_mode(&mut self) -> &mut EasyModeN<N> {
+        self.easy
+    }
+
+    /// Load as much data from the source as will fit. Returns false if nothing more could be loaded
+    fn fill(&mut self) -> bool {
+        let mut loaded = false;
+        while !self.eof {
+            if self.pending.is_empty() {
+                match self.source.next() {
+                    Some(data) => self.pending = data,
+                    None => self.eof = true,
+                }
+                continue;
+            }
+            let added = self.easy.add_data(self.pending);
+            self.pending = &self.pending[added..];
+            loaded |= added > 0;
+            if !self.pending.is_empty() {
+                // the buffer is full
+                break;
+            }
+        }
+        loaded
+    }
+
+    /// Decode the next frame into the output buffer, returning the number of samples or None at end of stream
+    fn next_frame(&mut self) -> Result<Option<usize>, EasyModeErr> {
+        // skip past the id3 tags and anything else up to the first mp3 sync tag
+        loop {
+            let more = self.fill();
+            if self.easy.mp3_decode_ready() {
+                break;
+            }
+            if !more && self.eof && !self.easy.mp3_decode_ready() {
+                return Ok(None);
+            }
+        }
+        loop {
+            self.fill();
+            match self.easy.decode(self.output) {
+                Ok(samples) => return Ok(Some(samples)),
+                Err(EasyModeErr::InDataUnderflow) if !self.eof => {
+                    if !self.fill() && !self.eof {
+                        // buffer is full and still doesn't hold a frame, so we can never decode it
+                        return Err(EasyModeErr::InDataUnderflow);
+                    }
+                }
+                // Whatever is left at the end of the stream can't be decoded, we're done
+                Err(_) if self.eof => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl<'a, const N: usize, S: Iterator<Item = &'a [u8]>> Iterator for Frames<'a, N, S> {
+    type Item = Result<usize, EasyModeErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        self.samples = 0;
+        match self.next_frame() {
+            Ok(Some(samples)) => {
+                self.samples = samples;
+                Some(Ok(samples))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Decode one frame from each of two streams and mix them together, eg to crossfade between tracks.
+///
+/// `mix_q8` sets the balance between the streams: 0 is all `a`, 256 is all `b`.
+/// Both streams must have the same sample rate and channel count and output the same number of samples
+/// from their next frame. Both frames are checked before either is decoded: if they don't line up, or one
+/// of them would output nothing (eg a frame that is trimmed, or dropped for missing bit reservoir data),
+/// neither stream is advanced and [EasyModeErr::FormatMismatch] is returned, so decode that stream on its
+/// own to get past it. Errors such as [EasyModeErr::InDataUnderflow] are also returned before anything is
+/// decoded, though a corrupt frame in `b` that only fails while decoding still loses `a`'s frame.
+/// `b` is decoded into its [scratch buffer](EasyModeN::with_scratch), which takes 4.5KiB of stack if none
+/// is set. Returns the number of samples written to `output_audio`
+pub fn crossfade<const A: usize, const B: usize>(
+    a: &mut EasyModeN<A>,
+    b: &mut EasyModeN<B>,
+    output_audio: &mut [i16],
+    mix_q8: u16,
+) -> Result<usize, EasyModeErr> {
+    let (frame_a, samples_a) = a.peek_decode()?;
+    let (frame_b, samples_b) = b.peek_decode()?;
+    if frame_a.outputSamps != frame_b.outputSamps
+        || frame_a.nChans != frame_b.nChans
+        || frame_a.samprate != frame_b.samprate
+        || samples_a == 0
+        || samples_a != samples_b
+    {
+        return Err(EasyModeErr::FormatMismatch);
+    }
+    let samples = a.decode(output_audio)?;
+    let mix_b = core::cmp::min(mix_q8, 256) as i32;
+    let mix_a = 256 - mix_b;
+    b.decode_scratch(
+        |_| true,
+        |samples_b, _| {
+            let samples = core::cmp::min(samples, samples_b.len());
+            for (out, b) in output_audio[..samples].iter_mut().zip(samples_b) {
+                let mixed = (*out as i32 * mix_a + *b as i32 * mix_b) >> 8;
+                *out = mixed.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+            }
+            samples
+        },
+    )
+}
+
+// The stream handling below is shared with EasyModeOpaqueN, so that the two front ends agree on how
+// a stream is synced and where its audio starts
+
+/// Skip the data at the start of `buffer` up to the next sync word followed by a valid frame header.
+/// Returns whether one was found and how many bytes were thrown away looking for it
+pub(crate) fn sync_to_frame<const N: usize>(
+    buffer: &mut contig_buffer::Buffer<N>,
+) -> (bool, usize) {
+    let mut discarded = 0;
+    while let Some(start) = Mp3::find_sync_word_offset(buffer.borrow_slice()) {
+        let header = buffer.borrow_slice().get(start..start + 4);
+        let valid = header
+            .and_then(|header| header.try_into().ok())
+            .map(Mp3::is_valid_frame_header);
+        let skip = if valid == Some(false) { start + 1 } else { start };
+        buffer.increment_start(skip);
+        discarded += skip;
+        match valid {
+            Some(true) => return (true, discarded),
+            // we can't check the header until more data arrives
+            None => return (false, discarded),
+            // not a real frame header, keep looking after it
+            Some(false) => {}
+        }
+    }
+    // Could not sync with any of the data in the buffer, so most of the data is useless.
+    // we could have 3 bytes of sync word, so keep the last 3 bytes
+    let discard = buffer.used().saturating_sub(3);
+    buffer.increment_start(discard);
+    (false, discarded + discard)
+}
+
+/// How many bytes at the start of the stream in `buffer` belong to an ID3v2 tag and need skipping,
+/// or 0 if there is no tag. Returns None while the tag's header may still be split across
+/// add_data calls
+pub(crate) fn id3v2_skip_len<const N: usize>(buffer: &contig_buffer::Buffer<N>) -> Option<usize> {
+    let data = buffer.borrow_slice();
+    let id3 = Mp3::find_id3v2(data);
+    if id3.is_none() && Mp3::partial_id3v2_at_start(data) && buffer.available() > 0 {
+        // wait for the rest of an ID3v2 header that was split across add_data calls
+        return None;
+    }
+    // start of header + header, extended header, id3v2 info and footer
+    Some(id3.map_or(0, |(offset, id3)| offset + id3.tag_len()))
+}
+
+/// Work out why the frame at the start of `data` could not be decoded.
+/// ThreePM rejects the headers of frames that aren't Layer 3, so report those as unsupported
+pub(crate) fn frame_err(data: &[u8], e: DecodeErr) -> EasyModeErr {
+    match Mp3::parse_version_layer(data) {
+        Some((version, layer)) if layer != Layer::Layer3 => {
+            EasyModeErr::UnsupportedLayer { version, layer }
+        }
+        _ => e.into(),
+    }
+}
+
+/// Average each pair of interleaved stereo samples into the start of `samples`.
+/// Returns the number of mono samples
+fn downmix_to_mono(samples: &mut [i16]) -> usize {
+    let mono_samples = samples.len() / 2;
+    for i in 0..mono_samples {
+        let (l, r) = (samples[2 * i], samples[2 * i + 1]);
+        samples[i] = ((l as i32 + r as i32) / 2) as i16;
+    }
+    mono_samples
+}
+
+/// Number of samples per channel in a frame
+fn samples_per_channel(frame: &MP3FrameInfo) -> usize {
+    if frame.nChans > 0 {
+        (frame.outputSamps / frame.nChans) as usize
+    } else {
+        0
+    }
+}
+
+/// Which ReplayGain adjustment [EasyMode::enable_replaygain] applies
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplayGainMode {
+    /// Normalise each track on its own
+    Track,
+    /// Normalise tracks as part of their album, preserving the loudness differences between them
+    Album,
+}
+
+/// How full the EasyMode internal MP3 stream buffer is, see [EasyModeN::buffer_stats]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferStats {
+    /// MP3 data in the buffer, see [EasyModeN::buffer_used]
+    pub used: usize,
+    /// Free space in the buffer, see [EasyModeN::buffer_free]
+    pub free: usize,
+    /// Free space at the end of the buffer. Adding more data than this shuffles the buffered data to
+    /// the start of the buffer first, see [EasyModeN::tail_contiguous_free]
+    pub contiguous_free: usize,
+    /// Size of the buffer
+    pub capacity: usize,
+}
+
+/// Errors that occur when calling the decode function
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EasyModeErr {
+    Okay,
+    InDataUnderflow,
+    MaindataUnderfow,
+    FreeBitrateSync,
+    OutOfMemory,
+    NullPointer,
+    InvalidFrameheader,
+    InvalidSideinfo,
+    InvalidScalefact,
+    InvalidHuffcodes,
+    InvalidDequantize,
+    InvalidImdct,
+    InvalidSubband,
+    Unknown,
+    InvalidError,
+    AudioBufferTooSmall,
+    /// Reading MP3 data from the data source failed
+    Io,
+    /// The stream reached its end loop point. Feed the stream again from the start to continue looping
+    LoopRestart,
+    /// Two streams that need to be decoded together have different formats
+    FormatMismatch,
+    /// The requested seek position is before the current position in the stream
+    InvalidSeek,
+    /// The next frame is valid MPEG audio, but not a version/layer combination that can be decoded
+    UnsupportedLayer { version: MpegVersion, layer: Layer },
+    /// Strict mode is enabled and the frame deviates from the MPEG audio spec
+    SpecViolation(SpecViolation),
+    /// There is more data in the MP3 stream buffer than `ThreePM` can be passed, see [MAX_BUFFER_SIZE]
+    BufferTooLarge,
+    /// The sample rate or channel count of the stream changed, see [EasyModeN::set_report_format_changes]
+    FormatChanged,
+    /// No frame has been synced to yet, add more data and try again
+    NotReady,
+}
+
+impl core::fmt::Display for EasyModeErr {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let msg = match self {
+            EasyModeErr::Okay => "no error",
+            EasyModeErr::InDataUnderflow => {
+                "MP3 input data underflow: need more bytes to hold a complete frame"
+            }
+            EasyModeErr::MaindataUnderfow => {
+                "MP3 main data underflow: need more bytes before this frame can be decoded"
+            }
+            EasyModeErr::FreeBitrateSync => "could not sync to a free-bitrate MP3 frame",
+            EasyModeErr::OutOfMemory => "MP3 decoder is out of memory",
+            EasyModeErr::NullPointer => "MP3 decoder was passed a null pointer",
+            EasyModeErr::InvalidFrameheader => "invalid MP3 frame header",
+            EasyModeErr::InvalidSideinfo => "invalid MP3 side info",
+            EasyModeErr::InvalidScalefact => "invalid MP3 scale factors",
+            EasyModeErr::InvalidHuffcodes => "invalid MP3 Huffman codes",
+            EasyModeErr::InvalidDequantize => "MP3 dequantization failed",
+            EasyModeErr::InvalidImdct => "MP3 IMDCT failed",
+            EasyModeErr::InvalidSubband => "MP3 subband synthesis failed",
+            EasyModeErr::Unknown => "unknown MP3 decoder error",
+            EasyModeErr::InvalidError => "MP3 decoder returned an unrecognised error code",
+            EasyModeErr::AudioBufferTooSmall => {
+                "output audio buffer is too small to hold the decoded frame"
+            }
+            EasyModeErr::Io => "failed to read MP3 data from the data source",
+            EasyModeErr::FormatMismatch => "MP3 streams have different audio formats",
+            EasyModeErr::LoopRestart => {
+                "reached the loop end, restart the stream from the beginning"
+            }
+            EasyModeErr::InvalidSeek => "cannot seek to before the current stream position",
+            EasyModeErr::BufferTooLarge => "MP3 stream buffer is too large to pass to the decoder",
+            EasyModeErr::FormatChanged => "MP3 stream changed sample rate or channel count",
+            EasyModeErr::NotReady => "no MP3 frame synced yet, more data is needed",
+            EasyModeErr::UnsupportedLayer { version, layer } => {
+                return write!(f, "{version} {layer} not supported");
+            }
+            EasyModeErr::SpecViolation(violation) => {
+                return write!(f, "MP3 frame breaks the spec: {violation}");
+            }
+        };
+        f.write_str(msg)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EasyModeErr {}
+
+impl From<DecodeErr> for EasyModeErr {
+    fn from(value: DecodeErr) -> Self {
+        match value {
+            DecodeErr::Okay => EasyModeErr::Okay,
+            DecodeErr::InDataUnderflow => EasyModeErr::InDataUnderflow,
+            DecodeErr::MaindataUnderfow => EasyModeErr::MaindataUnderfow,
+            DecodeErr::FreeBitrateSync => EasyModeErr::FreeBitrateSync,
+            DecodeErr::OutOfMemory => EasyModeErr::OutOfMemory,
+            DecodeErr::NullPointer => EasyModeErr::NullPointer,
+            DecodeErr::InvalidFrameheader => EasyModeErr::InvalidFrameheader,
+            DecodeErr::InvalidSideinfo => EasyModeErr::InvalidSideinfo,
+            DecodeErr::InvalidScalefact => EasyModeErr::InvalidScalefact,
+            DecodeErr::InvalidHuffcodes => EasyModeErr::InvalidHuffcodes,
+            DecodeErr::InvalidDequantize => EasyModeErr::InvalidDequantize,
+            DecodeErr::InvalidImdct => EasyModeErr::InvalidImdct,
+            DecodeErr::InvalidSubband => EasyModeErr::InvalidSubband,
+            DecodeErr::Unknown => EasyModeErr::Unknown,
+            DecodeErr::InvalidError => EasyModeErr::InvalidError,
+            DecodeErr::AudioBufferTooSmall => EasyModeErr::AudioBufferTooSmall,
+        }
+    }
+}
+
+/// An error from [EasyModeN::decode_checked], with the result of checking the frame's CRC
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CheckedDecodeErr {
+    /// Why the frame couldn't be decoded
+    pub err: EasyModeErr,
+    /// Whether the frame's CRC matched, or None if it isn't protected by a CRC or wasn't completely buffered
+    pub crc_ok: Option<bool>,
+}
+
+impl CheckedDecodeErr {
+    /// Whether the frame's CRC was checked. If it was and it matched, the error is unlikely to be from
+    /// corruption in transit
+    pub fn crc_checked(&self) -> bool {
+        self.crc_ok.is_some()
+    }
+}
+
+impl core::fmt::Display for CheckedDecodeErr {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self.crc_ok {
+            Some(true) => write!(f, "{} (CRC ok)", self.err),
+            Some(false) => write!(f, "{} (CRC mismatch)", self.err),
+            None => write!(f, "{} (CRC not checked)", self.err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CheckedDecodeErr {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_trim_drops_delay_and_padding() {
+        let mut frame = [0u8; 417];
+        frame[0..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        // Info tag counting 2 frames after itself
+        frame[36..48].copy_from_slice(b"Info\0\0\0\x01\0\0\0\x02");
+        let mut easy = EasyMode::new();
+        easy.add_data(&frame);
+        easy.set_trim(1000, 500);
+        easy.check_trim();
+        let info = Mp3::parse_frame_info(&frame).unwrap();
+        let mut out = [0i16; MAX_OUTPUT_SAMPLES];
+        // the Info frame and the delay are dropped, then the padding at the end of the last frame
+        let kept = [0; 4].map(|_| easy.process_output(&info, &mut out));
+        assert_eq!(kept, [0, 152 * 2, 652 * 2, 0]);
+    }
+
+    /// Two frames of 128kbps 44.1kHz stereo silence, followed by the header of the next frame
+    fn silent_frames() -> [u8; 417 * 2 + 4] {
+        let mut data = [0u8; 417 * 2 + 4];
+        for frame in data.chunks_mut(417) {
+            frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        }
+        data
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn warm_start_uses_scratch() {
+        let scratch = std::vec![0i16; MAX_OUTPUT_SAMPLES].leak();
+        let mut easy = EasyMode::with_scratch(scratch);
+        assert_eq!(easy.warm_start(&silent_frames()), 2);
+        // the scratch buffer is handed back for the next decode
+        easy.add_data(&silent_frames());
+        assert_eq!(easy.decode_borrowed().map(|samples| samples.len()), Ok(2304));
+    }
+
+    #[test]
+    fn crossfade_mixes_both_streams() {
+        let mut a = EasyMode::new();
+        let mut b = EasyMode::new();
+        a.add_data(&silent_frames());
+        b.add_data(&silent_frames());
+        let mut out = [0i16; MAX_OUTPUT_SAMPLES];
+        assert_eq!(crossfade(&mut a, &mut b, &mut out, 128), Ok(2304));
+        assert_eq!((a.buffer_used(), b.buffer_used()), (421, 421));
+    }
+
+    #[test]
+    fn crossfade_leaves_streams_when_one_cant_decode() {
+        let mut a = EasyMode::new();
+        let mut b = EasyMode::new();
+        a.add_data(&silent_frames());
+        // only part of `b`'s frame has arrived
+        b.add_data(&silent_frames()[..300]);
+        let mut out = [0i16; MAX_OUTPUT_SAMPLES];
+        assert_eq!(crossfade(&mut a, &mut b, &mut out, 128), Err(EasyModeErr::InDataUnderflow));
+        assert_eq!((a.buffer_used(), b.buffer_used()), (838, 300));
+        // `b`'s next frame is dropped for missing bit reservoir data, so it would output nothing
+        let mut data = silent_frames();
+        data[4] = 0x10;
+        let mut b = EasyMode::new();
+        b.add_data(&data);
+        assert_eq!(crossfade(&mut a, &mut b, &mut out, 128), Err(EasyModeErr::FormatMismatch));
+        assert_eq!((a.buffer_used(), b.buffer_used()), (838, 838));
+        // decoding `b` on its own gets past the dropped frame
+        assert_eq!(b.decode(&mut out), Ok(0));
+        assert_eq!(crossfade(&mut a, &mut b, &mut out, 128), Ok(2304));
+    }
+
+    #[test]
+    fn downmix_averages_pairs() {
+        let mut samples = [100, 300, i16::MAX, i16::MAX, i16::MIN, i16::MAX, -5, -6];
+        assert_eq!(downmix_to_mono(&mut samples), 4);
+        // full scale samples don't overflow or clip
+        assert_eq!(samples[..4], [200, i16::MAX, 0, -5]);
+    }
+
+    #[test]
+    fn id3v2_split_across_add_data() {
+        let mut data = [0u8; 64];
+        data[..10].copy_from_slice(&[b'I', b'D', b'3', 4, 0, 0, 0, 0, 0, 20]);
+        data[30..34].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        let mut easy = EasyMode::new();
+        assert!(!easy.mp3_decode_ready());
+        // not enough of the header to tell whether there's a tag yet
+        easy.add_data(&data[..5]);
+        assert!(!easy.mp3_decode_ready());
+        assert_eq!(easy.buffer_used(), 5);
+        easy.add_data(&data[5..]);
+        // skipping the tag and syncing take a call each
+        assert!((0..4).any(|_| easy.mp3_decode_ready()));
+        assert_eq!(easy.buffer_used(), 34);
+    }
+
+    #[test]
+    fn mp3_info_not_ready() {
+        let mut easy = EasyMode::new();
+        assert_eq!(easy.mp3_info(), Err(EasyModeErr::NotReady));
+        easy.add_data(&[0u8; 64]);
+        assert!(!easy.skip_to_next_sync_word());
+        assert_eq!(easy.mp3_info(), Err(EasyModeErr::NotReady));
+    }
+
+    #[test]
+    fn decode_checked_crc_mismatch() {
+        let mut frame = [0u8; 40];
+        // MPEG-1 Layer 3 mono with CRC, side info of 17 zero bytes but one
+        frame[0..4].copy_from_slice(&[0xFF, 0xFA, 0x90, 0xC0]);
+        frame[4..6].copy_from_slice(&[0xD0, 0xBF]);
+        frame[10] = 1;
+        let mut easy = EasyMode::new();
+        easy.add_data(&frame);
+        let mut out = [0i16; MAX_OUTPUT_SAMPLES];
+        let err = easy.decode_checked(&mut out).unwrap_err();
+        assert_eq!(err.err, EasyModeErr::SpecViolation(SpecViolation::CrcMismatch));
+        assert!(err.crc_checked());
+        assert_eq!(err.crc_ok, Some(false));
+        // the frame is left for the caller to deal with
+        assert_eq!(easy.buffer_used(), 40);
+    }
+
+    #[test]
+    fn decode_limited_stops_at_zero() {
+        let mut easy = EasyMode::new();
+        easy.add_data(&[0xFF, 0xFB, 0x90, 0x64]);
+        let mut out = [0i16; MAX_OUTPUT_SAMPLES];
+        let mut remaining = 0;
+        assert_eq!(easy.decode_limited(&mut out, &mut remaining), Ok(0));
+        assert_eq!(remaining, 0);
+        assert_eq!(easy.buffer_used(), 4);
+    }
+
+    #[test]
+    fn decode_limited_skips_dropped_frames() {
+        let mut data = [0u8; 417 * 4];
+        for frame in data.chunks_mut(417) {
+            frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        }
+        // the first frame's main data starts in the bit reservoir, so it is dropped
+        data[4] = 0x10;
+        let mut easy = EasyMode::new();
+        let mut input = &data[..];
+        let mut out = [0i16; MAX_OUTPUT_SAMPLES];
+        let mut remaining = 2;
+        let results = [0; 4].map(|_| {
+            input = &input[easy.add_data(input)..];
+            (easy.decode_limited(&mut out, &mut remaining), remaining)
+        });
+        assert_eq!(results, [(Ok(0), 2), (Ok(2304), 1), (Ok(2304), 0), (Ok(0), 0)]);
+        assert_eq!(easy.buffer_used(), 417);
+    }
+
+    #[test]
+    fn decode_borrowed_needs_scratch() {
+        let mut easy = EasyMode::new();
+        easy.add_data(&[0xFF, 0xFB, 0x90, 0x64]);
+        assert_eq!(easy.decode_borrowed(), Err(EasyModeErr::AudioBufferTooSmall));
+        assert_eq!(easy.buffer_used(), 4);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn fill_from_clamps_reader() {
+        struct Overreporting;
+        impl std::io::Read for Overreporting {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                Ok(buf.len() + 10)
+            }
+        }
+        let mut easy = EasyModeN::<16>::new();
+        assert_eq!(easy.fill_from(&mut Overreporting).unwrap(), 16);
+        assert_eq!(easy.buffer_used(), 16);
+        assert_eq!(easy.fill_from(&mut Overreporting).unwrap(), 0);
+    }
+
+    #[test]
+    fn try_add_data_reports_accepted() {
+        let mut easy = EasyModeN::<16>::new();
+        assert_eq!(easy.try_add_data(&[0u8; 10]), Ok(()));
+        // only the first 6 bytes fit
+        assert_eq!(easy.try_add_data(&[0u8; 10]), Err(6));
+        assert_eq!(easy.buffer_used(), 16);
+        assert_eq!(easy.try_add_data(&[]), Ok(()));
+        assert_eq!(easy.try_add_data(&[0u8; 1]), Err(0));
+    }
+
+    #[test]
+    fn push_and_decode_takes_what_fits() {
+        let mut easy = EasyModeN::<16>::new();
+        let mut out = [0i16; MAX_OUTPUT_SAMPLES];
+        let input = [0u8; 40];
+        assert_eq!(easy.push_and_decode(&input, &mut out), Ok((0, 16)));
+        // the junk was thrown away while looking for a sync word, making room for more
+        let (samples, consumed) = easy.push_and_decode(&input[16..], &mut out).unwrap();
+        assert_eq!(samples, 0);
+        assert!(consumed > 0);
+    }
+
+    #[test]
+    fn push_and_decode_error_reports_consumed() {
+        let mut input = [0u8; 64];
+        input[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        let mut easy = EasyMode::new();
+        // no room for the frame, so that decoding fails
+        let result = easy.push_and_decode(&input, &mut []);
+        assert!(matches!(result, Err((_, 64))), "{result:?}");
+    }
+
+    #[test]
+    fn buffer_stats_after_skip() {
+        let mut easy = EasyModeN::<64>::new();
+        easy.add_data(&[0u8; 40]);
+        easy.buffer_skip(10);
+        let stats = easy.buffer_stats();
+        assert_eq!(stats.used, 30);
+        assert_eq!(stats.free, 34);
+        assert_eq!(stats.contiguous_free, 24);
+        assert_eq!(stats.capacity, 64);
+    }
+
+    #[test]
+    fn finish_drops_partial_frame() {
+        let mut data = [0u8; 100];
+        data[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        let mut easy = EasyMode::new();
+        easy.add_data(&data);
+        let mut out = [0i16; MAX_OUTPUT_SAMPLES];
+        assert_eq!(easy.finish(&mut out), Ok(0));
+        assert_eq!(easy.buffer_used(), 0);
+        assert_eq!(easy.finish(&mut out), Ok(0));
+    }
+
+    #[test]
+    fn strict_mode_drops_bad_frames() {
+        let mut data = [0u8; 421];
+        // MPEG-1 Layer 3 mono with a CRC that doesn't match, then the next frame's header
+        data[0..4].copy_from_slice(&[0xFF, 0xFA, 0x90, 0xC0]);
+        data[4..6].copy_from_slice(&[0xD0, 0xBF]);
+        data[10] = 1;
+        data[417..].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        let mut easy = EasyMode::new();
+        easy.set_strict(true);
+        let mut out = [0i16; MAX_OUTPUT_SAMPLES];
+        // decode_unchecked parses the header in Rust, and the output can hold any frame
+        easy.add_data(&data[..400]);
+        assert_eq!(unsafe { easy.decode_unchecked(&mut out) }, Err(EasyModeErr::InDataUnderflow));
+        assert_eq!(easy.buffer_used(), 400);
+        easy.add_data(&data[400..]);
+        let violation = EasyModeErr::SpecViolation(SpecViolation::CrcMismatch);
+        assert_eq!(unsafe { easy.decode_unchecked(&mut out) }, Err(violation));
+        assert_eq!(easy.buffer_used(), 4);
+        assert_eq!(easy.position_samples(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn decode_all_skips_bad_frames() {
+        let mut data = [0xAAu8; 1000];
+        for offset in [100, 517] {
+            data[offset..offset + 4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        }
+        let mut easy = EasyMode::new();
+        assert!(easy.decode_all(&data).is_ok());
+        assert_eq!(easy.buffer_used(), 0);
+    }
+
+    #[test]
+    fn sync_word_counted_skip() {
+        let mut data = [0u8; 64];
+        data[13..17].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        let mut easy = EasyMode::new();
+        easy.add_data(&data);
+        assert_eq!(easy.skip_to_next_sync_word_counted(), Ok(13));
+        // nothing more to skip once synced
+        assert_eq!(easy.skip_to_next_sync_word_counted(), Ok(0));
+        assert_eq!(easy.buffer_used(), 51);
+        let mut easy = EasyMode::new();
+        easy.add_data(&[0u8; 20]);
+        assert_eq!(easy.skip_to_next_sync_word_counted(), Err(()));
+    }
+
+    #[test]
+    fn count_complete_frames() {
+        let mut data = [0u8; 1000];
+        for offset in [0, 417] {
+            data[offset..offset + 4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        }
+        let mut easy = EasyMode::new();
+        assert_eq!(easy.complete_frames_buffered(), 0);
+        easy.add_data(&data[..834]);
+        assert_eq!(easy.complete_frames_buffered(), 2);
+        easy.buffer_skip(1);
+        // the first frame header is gone, so only the second frame is found
+        assert_eq!(easy.complete_frames_buffered(), 1);
+        assert_eq!(easy.buffer_used(), 833);
+    }
+
+    #[test]
+    fn id3v2_in_small_chunks() {
+        let mut data = [0u8; 72];
+        data[..10].copy_from_slice(&[b'I', b'D', b'3', 3, 0, 0, 0, 0, 0, 20]);
+        data[30..34].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        let mut easy = EasyMode::new();
+        let mut ready = false;
+        for chunk in data.chunks(4) {
+            easy.add_data(chunk);
+            ready = easy.mp3_decode_ready();
+        }
+        assert!(ready || (0..4).any(|_| easy.mp3_decode_ready()));
+        assert_eq!(easy.buffer_used(), 42);
+    }
+
+    #[test]
+    fn no_id3v2_with_tail_like_a_tag() {
+        let mut data = [0u8; 20];
+        data[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        data[19] = b'I';
+        let mut easy = EasyMode::new();
+        easy.add_data(&data);
+        // an 'I' at the end of the buffer isn't the start of a tag, so sync isn't held up
+        assert!(easy.mp3_decode_ready());
+        assert_eq!(easy.buffer_used(), 20);
+    }
 }
@@ -0,0 +1,186 @@
+//! A low-memory version of [EasyMode](crate::easy_mode::EasyMode) for use with the `use-static-buffers` feature.
+//!
+//! [EasyModeOpaqueN] has the same core API as [EasyModeN](crate::easy_mode::EasyModeN), but uses
+//! [mp3_opaque::Mp3] so the decoder state lives in the C library's static buffers instead of the struct.
+//! That leaves little more than the MP3 stream buffer on the Rust side. The extras built on top of the
+//! decoder state (seeking, gapless trimming, ReplayGain, etc) are only available on `EasyModeN`.
+use crate::contig_buffer;
+use crate::easy_mode::{self, EasyModeErr, MAX_BUFFER_SIZE};
+use crate::mp3::MP3FrameInfo;
+use crate::mp3_opaque;
+
+/// A low-memory "easy mode" MP3 decoder, using the default 1024 byte MP3 stream buffer
+pub type EasyModeOpaque = EasyModeOpaqueN<1024>;
+
+/// A low-memory "easy mode" MP3 decoder with an `N` byte MP3 stream buffer.
+///
+/// The decoder state is shared by everything using the C library's static buffers, so only use one at a time.
+/// `N` must be no larger than [MAX_BUFFER_SIZE].
+pub struct EasyModeOpaqueN<const N: usize> {
+    mp3: mp3_opaque::Mp3,
+    buffer: contig_buffer::Buffer<N>,
+    sync: bool,
+    parsed_id3: bool,
+    bytes_to_skip: usize,
+    frame_info: Option<MP3FrameInfo>,
+}
+
+impl<const N: usize> EasyModeOpaqueN<N> {
+    /// Construct a new low-memory "easy mode" MP3 decoder.
+    /// Returns None if `ThreePM` couldn't provide the decoder state
+    pub fn new() -> Option<Self> {
+        debug_assert!(N <= MAX_BUFFER_SIZE, "EasyModeOpaque buffer is too large");
+        Some(EasyModeOpaqueN {
+            mp3: mp3_opaque::Mp3::new()?,
+            buffer: contig_buffer::Buffer::new(),
+            sync: false,
+            parsed_id3: false,
+            bytes_to_skip: 0,
+            frame_info: None,
+        })
+    }
+
+    /// Add MP3 data to the internal MP3 stream buffer.
+    /// Returns how many bytes were added, which is less than `data.len()` if the buffer is full
+    pub fn add_data(&mut self, data: &[u8]) -> usize {
+        self.buffer.load_slice(data)
+    }
+
+    /// Every mp3 frame starts with a sync word. Skip any data in buffer until the next sync word, and check if it's a valid frame.
+    /// Sync words followed by an invalid frame header are false positives, and are skipped over.
+    /// Returns true if it found a sync word, otherwise false
+    pub fn skip_to_next_sync_word(&mut self) -> bool {
+        if !self.sync {
+            self.sync = easy_mode::sync_to_frame(&mut self.buffer).0;
+        }
+        self.sync
+    }
+
+    /// How much data is free in the internal MP3 stream buffer
+    pub fn buffer_free(&self) -> usize {
+        self.buffer.available()
+    }
+
+    /// How much MP3 data is in the internal MP3 stream buffer
+    pub fn buffer_used(&self) -> usize {
+        self.buffer.used()
+    }
+
+    /// Skip over data in the buffer without decoding it
+    pub fn buffer_skip(&mut self, count: usize) -> usize {
+        let to_remove = core::cmp::min(self.buffer.used(), count);
+        self.buffer.increment_start(to_remove);
+        to_remove
+    }
+
+    /// Throw away all of the data in the buffer, eg when switching streams.
+    /// The decoder will need to find a sync word again before decoding
+    pub fn buffer_clear(&mut self) {
+        let _ = self.buffer.take_slice();
+        self.sync = false;
+    }
+
+    /// Skip over ID3 and anything else at the start of an MP3 stream.
+    /// Returns true when we've got a valid MP3 frame
+    pub fn mp3_decode_ready(&mut self) -> bool {
+        if self.buffer_used() == 0 {
+            false
+        } else {
+            if !self.parsed_id3 {
+                let Some(bytes_to_skip) = easy_mode::id3v2_skip_len(&self.buffer) else {
+                    return false;
+                };
+                self.parsed_id3 = true;
+                self.bytes_to_skip = bytes_to_skip;
+            };
+            if self.bytes_to_skip > 0 {
+                let skipped = self.buffer_skip(self.bytes_to_skip);
+                self.bytes_to_skip -= skipped;
+            } else {
+                let _ = self.skip_to_next_sync_word();
+            }
+            self.bytes_to_skip == 0 && self.sync
+        }
+    }
+
+    /// Decode the next MP3 audio frame after checking that the output buffer is large enough
+    pub fn decode(&mut self, output_audio: &mut [i16]) -> Result<usize, EasyModeErr> {
+        let buffered_data_len = self
+            .buffer
+            .used_c_int()
+            .ok_or(EasyModeErr::BufferTooLarge)?;
+        let next_frame = self
+            .mp3
+            .get_next_frame_info(self.buffer.borrow_slice())
+            .map_err(|e| easy_mode::frame_err(self.buffer.borrow_slice(), e))?;
+        let samples = next_frame.outputSamps.max(0) as usize;
+        if output_audio.len() < samples {
+            // Don't decode if there isn't enough space in the buffer
+            return Err(EasyModeErr::AudioBufferTooSmall);
+        }
+        let newlen = self
+            .mp3
+            .decode(self.buffer.borrow_slice(), buffered_data_len, output_audio)
+            .map_err(|e| easy_mode::frame_err(self.buffer.borrow_slice(), e))?;
+        self.buffer.consume_remaining(newlen);
+        self.frame_info = Some(next_frame);
+        Ok(samples)
+    }
+
+    /// Get MP3 metadata from the last MP3 frame decoded, or the frame at the start of the buffer.
+    /// Returns [EasyModeErr::NotReady] if the buffer hasn't been synced to a frame header yet, add more
+    /// data and call it again. Other errors mean the frame header there is invalid
+    pub fn mp3_info(&mut self) -> Result<MP3FrameInfo, EasyModeErr> {
+        if let Some(frameinfo) = self.frame_info {
+            Ok(frameinfo)
+        } else if !self.sync || self.buffer.used() < 4 {
+            Err(EasyModeErr::NotReady)
+        } else {
+            let frame = self.mp3.get_next_frame_info(self.buffer.borrow_slice())?;
+            Ok(frame)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp3::{Layer, MpegVersion};
+
+    // The decoder state is static, so this is one test rather than several that would run at once
+    #[test]
+    fn decode_after_id3v2_in_small_chunks() {
+        let mut data = [0u8; 20 + 417 * 2 + 4];
+        data[..10].copy_from_slice(&[b'I', b'D', b'3', 4, 0, 0, 0, 0, 0, 10]);
+        for frame in data[20..].chunks_mut(417) {
+            frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        }
+        let mut easy = EasyModeOpaque::new().unwrap();
+        let mut ready = false;
+        for chunk in data.chunks(4) {
+            easy.add_data(chunk);
+            ready = easy.mp3_decode_ready();
+            if ready {
+                break;
+            }
+        }
+        assert!(ready);
+        // the tag was skipped, rather than being decoded as audio
+        assert_eq!(easy.buffer_used(), 4);
+        let mut out = [0i16; crate::mp3::MAX_OUTPUT_SAMPLES];
+        assert_eq!(easy.add_data(&data[24..]), 417 * 2);
+        assert_eq!(easy.decode(&mut out), Ok(2304));
+        assert_eq!(easy.decode(&mut out), Ok(2304));
+        assert_eq!(easy.buffer_used(), 4);
+
+        // frames of other layers are reported as unsupported, like EasyMode does
+        let mut easy = EasyModeOpaque::new().unwrap();
+        easy.add_data(&[0xFF, 0xFD, 0x90, 0x64, 0, 0, 0, 0]);
+        let err = easy.decode(&mut out);
+        let layer2 = EasyModeErr::UnsupportedLayer {
+            version: MpegVersion::Mpeg1,
+            layer: Layer::Layer2,
+        };
+        assert_eq!(err, Err(layer2));
+    }
+}
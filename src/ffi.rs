@@ -40,7 +40,7 @@ pub const ERR_MP3_UNSUPPORTED: _bindgen_ty_1 = -13;
 pub const ERR_UNKNOWN: _bindgen_ty_1 = -9999;
 pub type _bindgen_ty_1 = core::ffi::c_int;
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct _MP3FrameInfo {
     pub bitrate: core::ffi::c_int,
     pub nChans: core::ffi::c_int,
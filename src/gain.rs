@@ -0,0 +1,137 @@
+//! Fixed-point gain stage applied to decoded samples.
+
+/// Unity gain in Q16 fixed point
+pub(crate) const UNITY_Q16: u32 = 1 << 16;
+
+// 10^(1/20), 10^(-1/20), 10^(1/200) and 10^(-1/200) in Q16.
+// Used to convert decibels to a linear gain without needing powf (which isn't in core)
+const UP_1DB: u64 = 73533;
+const DOWN_1DB: u64 = 58409;
+const UP_TENTH_DB: u64 = 66295;
+const DOWN_TENTH_DB: u64 = 64786;
+
+/// Convert a gain in tenths of a decibel to a linear gain in Q16 fixed point
+pub(crate) fn db_tenths_to_q16(db_tenths: i16) -> u32 {
+    let (step_db, step_tenth) = if db_tenths < 0 {
+        (DOWN_1DB, DOWN_TENTH_DB)
+    } else {
+        (UP_1DB, UP_TENTH_DB)
+    };
+    let magnitude = db_tenths.unsigned_abs();
+    let mut gain = UNITY_Q16 as u64;
+    for _ in 0..magnitude / 10 {
+        gain = (gain * step_db) >> 16;
+    }
+    for _ in 0..magnitude % 10 {
+        gain = (gain * step_tenth) >> 16;
+    }
+    gain.min(u32::MAX as u64) as u32
+}
+
+/// Scale a Q16 gain by a Q8 gain
+pub(crate) fn scale_q16_by_q8(gain_q16: u32, scale_q8: u16) -> u32 {
+    ((gain_q16 as u64 * scale_q8 as u64) >> 8).min(u32::MAX as u64) as u32
+}
+
+/// Multiply `sample` by `gain_q16`, saturating at the i16 limits. Returns true if it had to be clipped
+fn apply_sample(sample: &mut i16, gain_q16: u32) -> bool {
+    let scaled = (*sample as i64 * gain_q16 as i64) >> 16;
+    *sample = scaled.clamp(i16::MIN as i64, i16::MAX as i64) as i16;
+    scaled > i16::MAX as i64 || scaled < i16::MIN as i64
+}
+
+/// Multiply `samples` by `gain_q16`, saturating at the i16 limits.
+/// Returns the number of samples that had to be clipped
+pub(crate) fn apply(samples: &mut [i16], gain_q16: u32) -> u32 {
+    let mut clipped = 0;
+    for sample in samples.iter_mut() {
+        clipped += apply_sample(sample, gain_q16) as u32;
+    }
+    clipped
+}
+
+/// Multiply interleaved stereo `samples` by a separate gain for each channel, saturating at the i16 limits.
+/// Returns the number of samples that had to be clipped
+pub(crate) fn apply_stereo(samples: &mut [i16], left_q16: u32, right_q16: u32) -> u32 {
+    let mut clipped = 0;
+    for pair in samples.chunks_mut(2) {
+        if let [left, right] = pair {
+            clipped += apply_sample(left, left_q16) as u32;
+            clipped += apply_sample(right, right_q16) as u32;
+        }
+    }
+    clipped
+}
+
+/// Count the samples that are at the i16 limits, which is where clipped audio ends up
+pub(crate) fn count_full_scale(samples: &[i16]) -> u32 {
+    samples
+        .iter()
+        .filter(|sample| **sample == i16::MAX || **sample == i16::MIN)
+        .count() as u32
+}
+
+/// Peak absolute sample value of each channel of `samples`, which are interleaved if `channels` is 2.
+/// Mono samples give the same peak for both channels
+pub(crate) fn peaks(samples: &[i16], channels: usize) -> (i16, i16) {
+    fn peak<'a>(samples: impl Iterator<Item = &'a i16>) -> i16 {
+        samples.map(|sample| sample.saturating_abs()).max().unwrap_or(0)
+    }
+    if channels == 2 {
+        let left = peak(samples.iter().step_by(2));
+        let right = peak(samples.iter().skip(1).step_by(2));
+        (left, right)
+    } else {
+        let mono = peak(samples.iter());
+        (mono, mono)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_db_is_unity() {
+        assert_eq!(db_tenths_to_q16(0), UNITY_Q16);
+    }
+
+    #[test]
+    fn six_db_roughly_doubles() {
+        // +6.0dB is a factor of 1.995
+        let gain = db_tenths_to_q16(60);
+        assert!((130_000..131_000).contains(&gain));
+        // -6.0dB is a factor of 0.501
+        let gain = db_tenths_to_q16(-60);
+        assert!((32_700..33_000).contains(&gain));
+    }
+
+    #[test]
+    fn apply_saturates() {
+        let mut samples = [1000, -1000, 20000, -20000];
+        let clipped = apply(&mut samples, UNITY_Q16 * 2);
+        assert_eq!(samples, [2000, -2000, i16::MAX, i16::MIN]);
+        assert_eq!(clipped, 2);
+    }
+
+    #[test]
+    fn apply_stereo_per_channel() {
+        let mut samples = [1000, 1000, -1000, -1000];
+        let clipped = apply_stereo(&mut samples, UNITY_Q16 / 2, UNITY_Q16 * 3);
+        assert_eq!(samples, [500, 3000, -500, -3000]);
+        assert_eq!(clipped, 0);
+    }
+
+    #[test]
+    fn channel_peaks() {
+        assert_eq!(peaks(&[100, -300, -200, 50], 2), (200, 300));
+        assert_eq!(peaks(&[100, -300, i16::MIN], 1), (i16::MAX, i16::MAX));
+        assert_eq!(peaks(&[], 2), (0, 0));
+    }
+
+    #[test]
+    fn full_scale_samples() {
+        assert_eq!(count_full_scale(&[i16::MAX, 0, i16::MIN, i16::MAX - 1, i16::MIN + 1]), 2);
+        assert_eq!(count_full_scale(&[]), 0);
+    }
+}
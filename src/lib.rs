@@ -38,8 +38,11 @@
 //!
 //!     // We're past the header now, so we should be able to correctly decode an MP3 frame
 //!     // Metadata is stored in every frame, so check that now:
-//!     if let Ok(frame) = easy.mp3_info() {
-//!         println!("First MP3 frame info: {:?}", frame);
+//!     match easy.mp3_info() {
+//!         Ok(frame) => println!("First MP3 frame info: {:?}", frame),
+//!         // We ran out of data before finding a frame
+//!         Err(EasyModeErr::NotReady) => println!("No MP3 frame found yet"),
+//!         Err(e) => println!("Invalid MP3 frame: {e}"),
 //!     }
 //!     loop {
 //!         // if the buffer has space for another chunk of data from our source, load it
@@ -65,6 +68,11 @@
 //! ```
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
 // Allow the code generated by bindgen to break style rules
 #[allow(dead_code)]
 #[allow(non_camel_case_types)]
@@ -74,5 +82,19 @@
 pub mod ffi;
 
 mod contig_buffer;
+#[cfg(feature = "bench")]
+mod cycles;
+#[cfg(feature = "std")]
+pub mod decoder;
 pub mod easy_mode;
+#[cfg(feature = "use-static-buffers")]
+pub mod easy_mode_opaque;
+mod gain;
 pub mod mp3;
+#[cfg(feature = "use-static-buffers")]
+pub mod mp3_opaque;
+mod resample;
+#[cfg(feature = "symphonia")]
+pub mod symphonia;
+
+pub use easy_mode::crossfade;
@@ -1,7 +1,9 @@
 //! Hand-written Rust wrappers for `ThreePM`, as well as functionality not provided by `ThreePM` such as ID3v2 header skipping.
 use crate::ffi::{
     CriticalBandInfo, DequantInfo, FrameHeader, HuffmanInfo, IMDCTInfo, MP3DecInfo,
-    ScaleFactorInfo, ScaleFactorInfoSub, ScaleFactorJS, SideInfo, SideInfoSub, SubbandInfo,
+    MPEGVersion, MPEGVersion_MPEG1, MPEGVersion_MPEG2, MPEGVersion_MPEG25, ScaleFactorInfo,
+    ScaleFactorInfoSub, ScaleFactorJS, SideInfo, SideInfoSub, SubbandInfo, SIBYTES_MPEG1_MONO,
+    SIBYTES_MPEG1_STEREO, SIBYTES_MPEG2_MONO, SIBYTES_MPEG2_STEREO,
 };
 use core::ffi::c_void;
 
@@ -11,8 +13,18 @@ use core::ffi::c_void;
 /// This is handy for a streaming protocol, as you can fully recover a corrupted stream.
 ///
 /// MP3FrameInfo is returned by [get_last_frame_info](crate::mp3::Mp3::get_last_frame_info) and [get_next_frame_info](crate::mp3::Mp3::get_next_frame_info)
+///
+/// `version` is 0 for MPEG-1, 1 for MPEG-2 and 2 for MPEG-2.5, and `layer` is 1 to 3. Use
+/// [mpeg_version](MP3FrameInfo::mpeg_version) and [mpeg_layer](MP3FrameInfo::mpeg_layer) to get them as enums.
+/// `outputSamps` covers all channels: MPEG-2 and MPEG-2.5 Layer 3 frames have 576 samples per channel,
+/// half as many as MPEG-1
 pub use crate::ffi::_MP3FrameInfo as MP3FrameInfo;
 
+/// The largest number of samples a single MP3 frame can decode to (MPEG-1 Layer 3, stereo).
+/// An output buffer of this length is large enough for any frame.
+pub const MAX_OUTPUT_SAMPLES: usize =
+    (crate::ffi::MAX_NSAMP * crate::ffi::MAX_NGRAN * crate::ffi::MAX_NCHAN) as usize;
+
 /// ID3v2 option flags
 #[derive(Debug)]
 pub struct Id3v2Flags {
@@ -54,6 +66,338 @@ pub struct Id3v2 {
     pub size: usize,
 }
 
+impl Id3v2 {
+    /// Total number of bytes the tag takes up in the stream, starting from the "ID3" marker.
+    ///
+    /// The tag size already covers the extended header and any padding, so only the 10 byte
+    /// header and the optional 10 byte footer need to be added to it. Footers were added in ID3v2.4,
+    /// so the flag is ignored for older versions
+    pub fn tag_len(&self) -> usize {
+        let has_footer = self.flags.footer_present && self.version == Id3v2Version::ID3v2_4;
+        let footer = if has_footer { 10 } else { 0 };
+        10 + self.size + footer
+    }
+
+    /// Iterate over the frames (TIT2, TPE1, etc) of the tag in `tag_bytes`, which starts with the tag header
+    /// found by [Mp3::find_id3v2].
+    ///
+    /// Each item is the frame ID and the frame's data. ID3v2.2 frame IDs are 3 bytes, so the last byte of
+    /// the ID is 0. Unsynchronisation is not undone, and iteration stops at the padding after the last
+    /// frame or at the end of `tag_bytes` if it doesn't hold the whole tag
+    pub fn frames<'a>(&self, tag_bytes: &'a [u8]) -> Id3FrameIter<'a> {
+        let end = tag_bytes.len().min(10 + self.size);
+        let mut body = tag_bytes.get(10..end).unwrap_or(&[]);
+        if self.flags.extended_header {
+            let extended_len = match (self.version == Id3v2Version::ID3v2_4, body) {
+                // the ID3v2.4 extended header size is syncsafe and includes the size field
+                (true, [s1, s2, s3, s4, ..]) => syncsafe([*s1, *s2, *s3, *s4]),
+                (false, [s1, s2, s3, s4, ..]) => {
+                    4 + u32::from_be_bytes([*s1, *s2, *s3, *s4]) as usize
+                }
+                _ => body.len(),
+            };
+            body = body.get(extended_len..).unwrap_or(&[]);
+        }
+        Id3FrameIter {
+            data: body,
+            v2_2: self.version == Id3v2Version::ID3v2_2,
+            syncsafe_sizes: self.version == Id3v2Version::ID3v2_4,
+        }
+    }
+}
+
+/// Decode a 28 bit syncsafe integer, where the top bit of each byte is always zero
+fn syncsafe(bytes: [u8; 4]) -> usize {
+    let [s1, s2, s3, s4] = bytes.map(|b| (b & 0x7F) as usize);
+    s4 | s3 << 7 | s2 << 14 | s1 << 21
+}
+
+/// Iterator over the frames of an ID3v2 tag, returned by [Id3v2::frames]
+pub struct Id3FrameIter<'a> {
+    data: &'a [u8],
+    v2_2: bool,
+    syncsafe_sizes: bool,
+}
+
+impl<'a> Iterator for Id3FrameIter<'a> {
+    type Item = ([u8; 4], &'a [u8]);
+
+    // ID3v2.3 and ID3v2.4 frame headers are the ID (4), size (4) and flags (2).
+    // ID3v2.4 sizes are syncsafe. ID3v2.2 frame headers are the ID (3) and size (3)
+    fn next(&mut self) -> Option<Self::Item> {
+        let (id, size, header_len): ([u8; 4], usize, usize) = match (self.v2_2, self.data) {
+            (true, [i1, i2, i3, s1, s2, s3, ..]) => {
+                let size = u32::from_be_bytes([0, *s1, *s2, *s3]) as usize;
+                ([*i1, *i2, *i3, 0], size, 6)
+            }
+            (false, [i1, i2, i3, i4, s1, s2, s3, s4, _, _, ..]) => {
+                let size = if self.syncsafe_sizes {
+                    syncsafe([*s1, *s2, *s3, *s4])
+                } else {
+                    u32::from_be_bytes([*s1, *s2, *s3, *s4]) as usize
+                };
+                ([*i1, *i2, *i3, *i4], size, 10)
+            }
+            _ => return None,
+        };
+        // frame IDs are upper case letters and digits, anything else is padding or garbage
+        let id_len = if self.v2_2 { 3 } else { 4 };
+        if !id[..id_len].iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit()) {
+            self.data = &[];
+            return None;
+        }
+        let Some(frame) = self.data.get(header_len..header_len.saturating_add(size)) else {
+            self.data = &[];
+            return None;
+        };
+        self.data = &self.data[header_len + frame.len()..];
+        Some((id, frame))
+    }
+}
+
+/// APE tag info extracted from an APEv2 header or an APEv1/APEv2 footer
+#[derive(Debug, PartialEq, Eq)]
+pub struct ApeTag {
+    /// 1000 for APEv1, 2000 for APEv2
+    pub version: u32,
+    /// Total size of the tag in bytes, including its header and footer
+    pub size: usize,
+    /// Number of items in the tag
+    pub items: u32,
+    /// The tag has a header before its items. APEv1 tags only ever have a footer
+    pub has_header: bool,
+}
+
+/// ID3v1 info extracted from the 128 byte tag at the end of a stream.
+/// Text fields are trimmed of trailing nulls and spaces, and are usually Latin-1 encoded
+#[derive(Debug)]
+pub struct Id3v1<'a> {
+    /// Song title
+    pub title: &'a [u8],
+    /// Artist name
+    pub artist: &'a [u8],
+    /// Album name
+    pub album: &'a [u8],
+    /// Release year, normally 4 ASCII digits
+    pub year: &'a [u8],
+    /// Comment
+    pub comment: &'a [u8],
+    /// Track number, only present in ID3v1.1 tags
+    pub track: Option<u8>,
+    /// Index into the ID3v1 genre list, 255 if unset
+    pub genre: u8,
+}
+
+/// Stream info from the Xing/Info header in the first frame of a VBR (or LAME encoded CBR) stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VbrInfo {
+    /// Number of audio frames in the stream, excluding the frame holding this header
+    pub frames: Option<u32>,
+    /// Number of bytes in the stream, including this frame
+    pub bytes: Option<u32>,
+    /// Seek table: entry `i` is the position `i` percent of the way through the track,
+    /// as a fraction of `bytes` out of 256
+    pub toc: Option<[u8; 100]>,
+    /// Encoder quality indicator, 0 (best) to 100 (worst)
+    pub quality: Option<u32>,
+    /// True for a Xing tag. LAME writes an Info tag instead for CBR streams
+    pub vbr: bool,
+    /// Number of samples of silence the encoder added at the start of the stream, from the LAME tag.
+    /// 0 if there is no LAME tag
+    pub encoder_delay: u16,
+    /// Number of samples of padding the encoder added at the end of the stream, from the LAME tag.
+    /// 0 if there is no LAME tag
+    pub encoder_padding: u16,
+}
+
+impl VbrInfo {
+    /// Byte offset of the position `percent` (0 to 100) of the way through the track, using the seek table.
+    /// The offset is counted from the start of the frame holding this header.
+    /// Returns None if the header doesn't have both a seek table and the stream length in bytes
+    pub fn seek_byte_for_percent(&self, percent: f32) -> Option<u64> {
+        self.seek_byte_in(percent, self.bytes? as u64)
+    }
+
+    /// Like [seek_byte_for_percent](Self::seek_byte_for_percent), for a stream that is `total_bytes` long.
+    /// Returns None if the header doesn't have a seek table
+    pub fn seek_byte_in(&self, percent: f32, total_bytes: u64) -> Option<u64> {
+        let toc = self.toc.as_ref()?;
+        let percent = percent.clamp(0.0, 100.0);
+        // interpolate between the entries either side of `percent`, the end of the track is 256
+        let index = (percent as usize).min(99);
+        let start = toc[index] as f32;
+        let end = toc.get(index + 1).map_or(256.0, |&end| end as f32);
+        let fraction = (start + (end - start) * (percent - index as f32)) / 256.0;
+        Some((fraction * total_bytes as f32) as u64)
+    }
+}
+
+/// Whether a stream is constant or variable bitrate, found by [Mp3::detect_bitrate_mode]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitrateMode {
+    /// Every frame has the same bitrate, in bps
+    Constant(u32),
+    /// The bitrate changes between frames
+    Variable,
+    /// There weren't enough frames to tell
+    Unknown,
+}
+
+/// Ways a frame can deviate from the MPEG audio spec, found by [Mp3::check_conformance]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SpecViolation {
+    /// The header uses the reserved bitrate or sample rate index
+    ReservedIndex,
+    /// The header uses the reserved emphasis value
+    ReservedEmphasis,
+    /// The frame CRC doesn't match its header and side info
+    CrcMismatch,
+    /// The frame length in the header doesn't lead to the next frame
+    FrameSizeMismatch,
+}
+
+impl core::fmt::Display for SpecViolation {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let msg = match self {
+            SpecViolation::ReservedIndex => "reserved bitrate or sample rate index",
+            SpecViolation::ReservedEmphasis => "reserved emphasis value",
+            SpecViolation::CrcMismatch => "frame CRC mismatch",
+            SpecViolation::FrameSizeMismatch => "frame length doesn't lead to the next frame",
+        };
+        f.write_str(msg)
+    }
+}
+
+/// MPEG audio version of a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MpegVersion {
+    /// MPEG-1
+    Mpeg1,
+    /// MPEG-2
+    Mpeg2,
+    /// MPEG-2.5, an unofficial extension of MPEG-2 for low sample rates
+    Mpeg25,
+}
+
+impl core::fmt::Display for MpegVersion {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            MpegVersion::Mpeg1 => write!(f, "MPEG-1"),
+            MpegVersion::Mpeg2 => write!(f, "MPEG-2"),
+            MpegVersion::Mpeg25 => write!(f, "MPEG-2.5"),
+        }
+    }
+}
+
+/// Channel mode of a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChannelMode {
+    /// Two independently coded channels
+    Stereo,
+    /// Two channels coded together using intensity and/or M/S stereo, see [Mp3::mode_ext]
+    JointStereo,
+    /// Two unrelated mono channels, eg two languages
+    DualChannel,
+    /// One channel
+    Mono,
+}
+
+/// De-emphasis that should be applied to a frame's audio after decoding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Emphasis {
+    /// No emphasis
+    None,
+    /// 50/15 microsecond emphasis
+    FiftyFifteen,
+    /// The reserved emphasis value
+    Reserved,
+    /// CCITT J.17 emphasis
+    CcittJ17,
+}
+
+/// The fields of a frame header, with the lookup table indexes decoded to their values.
+/// See [Mp3::frame_header]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DecodedHeader {
+    pub version: MpegVersion,
+    pub layer: Layer,
+    /// Bitrate in kbps, or 0 for a free format frame
+    pub bitrate_kbps: u32,
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    pub channel_mode: ChannelMode,
+    /// Raw mode extension, see [Mp3::mode_ext]
+    pub mode_extension: u8,
+    pub emphasis: Emphasis,
+    /// The frame is followed by a 16 bit CRC
+    pub crc: bool,
+    /// The frame has an extra padding slot
+    pub padding: bool,
+    /// The private bit, which is free for applications to use
+    pub private: bool,
+    pub copyright: bool,
+    /// The audio is an original rather than a copy
+    pub original: bool,
+}
+
+/// MPEG audio layer of a frame. ThreePM can only decode Layer 3
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Layer {
+    /// Layer I
+    Layer1,
+    /// Layer II
+    Layer2,
+    /// Layer III, aka MP3
+    Layer3,
+}
+
+impl core::fmt::Display for Layer {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Layer::Layer1 => write!(f, "Layer I"),
+            Layer::Layer2 => write!(f, "Layer II"),
+            Layer::Layer3 => write!(f, "Layer III"),
+        }
+    }
+}
+
+// Bitrates in kbps, indexed by the bitrate index of the frame header. Index 0 is "free format", 15 is invalid
+const BITRATES_MPEG1: [[u16; 15]; 3] = [
+    // Layer I
+    [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448],
+    // Layer II
+    [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384],
+    // Layer III
+    [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320],
+];
+const BITRATES_MPEG2: [[u16; 15]; 3] = [
+    // Layer I
+    [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256],
+    // Layer II
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    // Layer III
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+// Sample rates in Hz, indexed by the sample rate index of the frame header. Index 3 is reserved
+const SAMPLE_RATES_MPEG1: [u32; 3] = [44100, 48000, 32000];
+const SAMPLE_RATES_MPEG2: [u32; 3] = [22050, 24000, 16000];
+const SAMPLE_RATES_MPEG25: [u32; 3] = [11025, 12000, 8000];
+
+/// ReplayGain adjustments stored in the LAME tag of the first frame, in tenths of a decibel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReplayGain {
+    /// Gain to normalise this track on its own ("radio" gain)
+    pub track: Option<i16>,
+    /// Gain to normalise this track as part of its album ("audiophile" gain)
+    pub album: Option<i16>,
+}
+
 impl MP3FrameInfo {
     pub fn new() -> MP3FrameInfo {
         MP3FrameInfo {
@@ -67,6 +411,87 @@ impl MP3FrameInfo {
             size: 0,
         }
     }
+
+    /// Construct the frame info for a frame with the given format, eg to compare against in tests.
+    ///
+    /// The output sample count, 16 bits per sample and the frame size (without a padding byte, 0 for
+    /// free format) are worked out from the format
+    pub fn with_fields(
+        version: MpegVersion,
+        layer: Layer,
+        bitrate: u32,
+        sample_rate: u32,
+        channels: u8,
+    ) -> MP3FrameInfo {
+        let samples = match (layer, version) {
+            (Layer::Layer1, _) => 384,
+            (Layer::Layer2, _) | (Layer::Layer3, MpegVersion::Mpeg1) => 1152,
+            (Layer::Layer3, _) => 576,
+        };
+        let size = Mp3::frame_size(version, layer, bitrate as usize, sample_rate as usize, 0);
+        MP3FrameInfo {
+            bitrate: bitrate as i32,
+            nChans: channels as i32,
+            samprate: sample_rate as i32,
+            bitsPerSample: 16,
+            outputSamps: samples * channels as i32,
+            layer: match layer {
+                Layer::Layer1 => 1,
+                Layer::Layer2 => 2,
+                Layer::Layer3 => 3,
+            },
+            version: match version {
+                MpegVersion::Mpeg1 => MPEGVersion_MPEG1,
+                MpegVersion::Mpeg2 => MPEGVersion_MPEG2,
+                MpegVersion::Mpeg25 => MPEGVersion_MPEG25,
+            } as i32,
+            size: size.unwrap_or(0) as i32,
+        }
+    }
+
+    /// The WAV format to write this frame's decoded samples with, eg for a `hound::WavWriter`
+    #[cfg(feature = "hound")]
+    pub fn to_wav_spec(&self) -> hound::WavSpec {
+        hound::WavSpec {
+            channels: self.nChans as u16,
+            sample_rate: self.samprate as u32,
+            bits_per_sample: self.bitsPerSample as u16,
+            sample_format: hound::SampleFormat::Int,
+        }
+    }
+
+    /// How long this frame plays for, in microseconds. Returns 0 if the frame info isn't populated
+    pub fn frame_duration_us(&self) -> u32 {
+        let samples_per_second = self.samprate.max(0) as u64 * self.nChans.max(0) as u64;
+        (self.outputSamps.max(0) as u64 * 1_000_000)
+            .checked_div(samples_per_second)
+            .unwrap_or(0) as u32
+    }
+
+    /// MPEG audio layer of this frame.
+    /// Frame info that hasn't been filled in reports Layer 3, the only layer ThreePM decodes
+    pub fn mpeg_layer(&self) -> Layer {
+        match self.layer {
+            1 => Layer::Layer1,
+            2 => Layer::Layer2,
+            _ => Layer::Layer3,
+        }
+    }
+
+    /// MPEG audio version of this frame.
+    /// Frame info that hasn't been filled in reports MPEG-1, as ThreePM numbers it 0
+    pub fn mpeg_version(&self) -> MpegVersion {
+        match self.version as MPEGVersion {
+            crate::ffi::MPEGVersion_MPEG2 => MpegVersion::Mpeg2,
+            crate::ffi::MPEGVersion_MPEG25 => MpegVersion::Mpeg25,
+            _ => MpegVersion::Mpeg1,
+        }
+    }
+
+    /// How many bytes of decoded audio this frame produces
+    pub fn bytes_per_frame(&self) -> usize {
+        self.outputSamps.max(0) as usize * self.bitsPerSample.max(0) as usize / 8
+    }
 }
 
 impl Default for MP3FrameInfo {
@@ -74,8 +499,32 @@ impl Default for MP3FrameInfo {
         Self::new()
     }
 }
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for MP3FrameInfo {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "{=i32}bps {=i32}Hz {=i32}ch layer {=i32}",
+            self.bitrate,
+            self.samprate,
+            self.nChans,
+            self.layer
+        )
+    }
+}
+/// What decoding a single frame with [Mp3::decode_frame] produced
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameResult {
+    /// Number of samples written to the output buffer
+    pub samples: usize,
+    /// Number of bytes of MP3 data used to decode the frame
+    pub bytes_consumed: usize,
+}
+
 /// Errors that occur when calling the decode function
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum DecodeErr {
     Okay,
     InDataUnderflow,
@@ -92,6 +541,8 @@ pub enum DecodeErr {
     InvalidSubband,
     Unknown,
     InvalidError,
+    /// The output buffer is too small to hold the decoded frame
+    AudioBufferTooSmall,
 }
 
 impl From<i32> for DecodeErr {
@@ -117,6 +568,39 @@ impl From<i32> for DecodeErr {
     }
 }
 
+impl core::fmt::Display for DecodeErr {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let msg = match self {
+            DecodeErr::Okay => "no error",
+            DecodeErr::InDataUnderflow => {
+                "MP3 input data underflow: need more bytes to hold a complete frame"
+            }
+            DecodeErr::MaindataUnderfow => {
+                "MP3 main data underflow: need more bytes before this frame can be decoded"
+            }
+            DecodeErr::FreeBitrateSync => "could not sync to a free-bitrate MP3 frame",
+            DecodeErr::OutOfMemory => "MP3 decoder is out of memory",
+            DecodeErr::NullPointer => "MP3 decoder was passed a null pointer",
+            DecodeErr::InvalidFrameheader => "invalid MP3 frame header",
+            DecodeErr::InvalidSideinfo => "invalid MP3 side info",
+            DecodeErr::InvalidScalefact => "invalid MP3 scale factors",
+            DecodeErr::InvalidHuffcodes => "invalid MP3 Huffman codes",
+            DecodeErr::InvalidDequantize => "MP3 dequantization failed",
+            DecodeErr::InvalidImdct => "MP3 IMDCT failed",
+            DecodeErr::InvalidSubband => "MP3 subband synthesis failed",
+            DecodeErr::Unknown => "unknown MP3 decoder error",
+            DecodeErr::InvalidError => "MP3 decoder returned an unrecognised error code",
+            DecodeErr::AudioBufferTooSmall => {
+                "output audio buffer is too small to hold the decoded frame"
+            }
+        };
+        f.write_str(msg)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeErr {}
+
 /// MP3 decoding context object
 ///
 /// This struct contains all of the data structures required for the
@@ -125,8 +609,12 @@ impl From<i32> for DecodeErr {
 ///
 /// Note: this struct is very large by embedded standards (~24KB).
 /// Plan accordingly.
+///
+/// As all of the decoder state is stored in this struct, cloning it takes a complete snapshot of the
+/// decoder (including the overlap and subband buffers). Restore the snapshot with `clone_from`, eg to
+/// undo a speculative decode. This isn't possible if the C library allocates the decoder state itself.
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Mp3 {
     mp3_dec_info: MP3DecInfo,
 }
@@ -226,9 +714,37 @@ impl Mp3 {
         Self { mp3_dec_info }
     }
 
+    /// Clear the state carried over between frames (the bit reservoir, IMDCT overlap and subband
+    /// synthesis history), so that decoding can start on a new stream without the end of the previous
+    /// one bleeding into the first frames
+    pub fn reset(&mut self) {
+        let info = &mut self.mp3_dec_info;
+        info.freeBitrateFlag = 0;
+        info.freeBitrateSlots = 0;
+        info.mainDataBegin = 0;
+        info.mainDataBytes = 0;
+        info.mi.overBuf = [[0; 288]; 2];
+        info.mi.numPrevIMDCT = [0; 2];
+        info.mi.prevType = [0; 2];
+        info.mi.prevWinSwitch = [0; 2];
+        info.sbi.vbuf = [0; 2176];
+        info.sbi.vindex = 0;
+    }
+
     /// Find the offset of the next sync word in the MP3 stream. Use this to find the next frame
+    ///
+    /// Returns -1 if there is no sync word, as the C function does.
+    /// Prefer [find_sync_word_offset](Self::find_sync_word_offset), which can't be mistaken for an offset
     pub fn find_sync_word(mp3buf: &[u8]) -> i32 {
-        unsafe { crate::ffi::MP3FindSyncWord(mp3buf.as_ptr(), mp3buf.len() as i32) }
+        // ThreePM takes an i32 length, so only search as much as it can be passed
+        let len = mp3buf.len().min(i32::MAX as usize) as i32;
+        unsafe { crate::ffi::MP3FindSyncWord(mp3buf.as_ptr(), len) }
+    }
+
+    /// Find the offset of the next sync word in the MP3 stream, or None if there isn't one.
+    /// Use this to find the next frame
+    pub fn find_sync_word_offset(mp3buf: &[u8]) -> Option<usize> {
+        usize::try_from(Self::find_sync_word(mp3buf)).ok()
     }
 
     /// Get info for the most recently decoded MP3 frame
@@ -276,6 +792,474 @@ impl Mp3 {
         }
     }
 
+    /// Decode the frame at the start of `frame` into `output_audio`.
+    ///
+    /// This decodes a frame from any slice without needing a stream buffer, eg to test a specific problem frame.
+    /// Frames that use the bit reservoir need the frames before them to have been decoded first.
+    /// Returns [DecodeErr::AudioBufferTooSmall] without decoding if `output_audio` can't hold the frame
+    pub fn decode_frame(
+        &mut self,
+        frame: &[u8],
+        output_audio: &mut [i16],
+    ) -> Result<FrameResult, DecodeErr> {
+        let info = self.get_next_frame_info(frame)?;
+        if output_audio.len() < info.outputSamps.max(0) as usize {
+            return Err(DecodeErr::AudioBufferTooSmall);
+        }
+        // ThreePM takes an i32 length, so only pass as much as it can be given
+        let len = frame.len().min(i32::MAX as usize) as i32;
+        let newlen = self.decode(frame, len, output_audio)?;
+        Ok(FrameResult {
+            samples: self.get_last_frame_info().outputSamps as usize,
+            bytes_consumed: (len - newlen) as usize,
+        })
+    }
+
+    /// Decode the next MP3 frame straight from `source`, then advance `source` past the bytes consumed.
+    /// Returns the number of samples written to `output_audio`.
+    ///
+    /// This is for decoding an MP3 that is already in memory without copying it into a stream buffer.
+    /// `source` must start on a frame, eg after skipping to [find_sync_word](Self::find_sync_word).
+    /// Returns [DecodeErr::AudioBufferTooSmall] without decoding if `output_audio` can't hold the frame
+    pub fn decode_in_place(
+        &mut self,
+        source: &mut &[u8],
+        output_audio: &mut [i16],
+    ) -> Result<usize, DecodeErr> {
+        let result = self.decode_frame(source, output_audio)?;
+        *source = &source[result.bytes_consumed..];
+        Ok(result.samples)
+    }
+
+    // Frame header format is as follows
+    // AAAAAAAA AAABBCCD EEEEFFGH IIJJKLMM
+    // A is the sync word, B is the version, C is the layer.
+    //
+    /// Read the MPEG version and layer from the frame header at the start of `header`.
+    /// Returns None if there's no sync word or either field holds a reserved value
+    pub fn parse_version_layer(header: &[u8]) -> Option<(MpegVersion, Layer)> {
+        let [b0, b1, ..] = header else {
+            return None;
+        };
+        if *b0 != 0xFF || b1 & 0b1110_0000 != 0b1110_0000 {
+            return None;
+        }
+        let version = match (b1 >> 3) & 0b11 {
+            0b00 => MpegVersion::Mpeg25,
+            0b10 => MpegVersion::Mpeg2,
+            0b11 => MpegVersion::Mpeg1,
+            _ => return None,
+        };
+        let layer = match (b1 >> 1) & 0b11 {
+            0b01 => Layer::Layer3,
+            0b10 => Layer::Layer2,
+            0b11 => Layer::Layer1,
+            _ => return None,
+        };
+        Some((version, layer))
+    }
+
+    /// Look up the bitrate (in bps), sample rate and padding of the frame whose header is at the start of `header`
+    fn header_rates(header: &[u8]) -> Option<(MpegVersion, Layer, usize, usize, usize)> {
+        let (version, layer) = Self::parse_version_layer(header)?;
+        let b2 = *header.get(2)?;
+        let (bitrate, sample_rate) =
+            Self::lookup_rates(version, layer, (b2 >> 4) as usize, ((b2 >> 2) & 0b11) as usize)?;
+        let padding = ((b2 >> 1) & 1) as usize;
+        Some((version, layer, bitrate as usize * 1000, sample_rate as usize, padding))
+    }
+
+    /// Look up the bitrate (in kbps) and sample rate of a frame header's bitrate and sample rate indexes
+    fn lookup_rates(
+        version: MpegVersion,
+        layer: Layer,
+        bitrate_index: usize,
+        sample_rate_index: usize,
+    ) -> Option<(u32, u32)> {
+        let bitrates = match version {
+            MpegVersion::Mpeg1 => &BITRATES_MPEG1,
+            MpegVersion::Mpeg2 | MpegVersion::Mpeg25 => &BITRATES_MPEG2,
+        };
+        let layer_bitrates = match layer {
+            Layer::Layer1 => &bitrates[0],
+            Layer::Layer2 => &bitrates[1],
+            Layer::Layer3 => &bitrates[2],
+        };
+        let bitrate = *layer_bitrates.get(bitrate_index)? as u32;
+        let sample_rates = match version {
+            MpegVersion::Mpeg1 => &SAMPLE_RATES_MPEG1,
+            MpegVersion::Mpeg2 => &SAMPLE_RATES_MPEG2,
+            MpegVersion::Mpeg25 => &SAMPLE_RATES_MPEG25,
+        };
+        let sample_rate = *sample_rates.get(sample_rate_index)?;
+        Some((bitrate, sample_rate))
+    }
+
+    /// Check that a frame header has a legal version, layer, bitrate index and sample rate index,
+    /// to weed out false positives from [find_sync_word](Self::find_sync_word)
+    pub fn is_valid_frame_header(bytes: &[u8; 4]) -> bool {
+        Self::header_rates(bytes).is_some()
+    }
+
+    /// Calculate the length in bytes of the frame with this header, including the padding byte, without
+    /// needing a decoder, eg for scanning frames or building a seek index.
+    /// Returns None if the header is invalid or the frame is free format
+    pub fn frame_size_bytes(header: &[u8; 4]) -> Option<usize> {
+        Self::frame_length(header)
+    }
+
+    /// Calculate the length in bytes of the frame whose header is at the start of `header`.
+    /// Returns None if the header is invalid or the frame is free format
+    fn frame_length(header: &[u8]) -> Option<usize> {
+        let (version, layer, bitrate, sample_rate, padding) = Self::header_rates(header)?;
+        Self::frame_size(version, layer, bitrate, sample_rate, padding)
+    }
+
+    /// Length in bytes of a frame with the given format (bitrate in bps). Returns None for free format frames
+    fn frame_size(
+        version: MpegVersion,
+        layer: Layer,
+        bitrate: usize,
+        sample_rate: usize,
+        padding: usize,
+    ) -> Option<usize> {
+        if bitrate == 0 || sample_rate == 0 {
+            return None;
+        }
+        let length = match (layer, version) {
+            (Layer::Layer1, _) => (12 * bitrate / sample_rate + padding) * 4,
+            (Layer::Layer2, _) | (Layer::Layer3, MpegVersion::Mpeg1) => {
+                144 * bitrate / sample_rate + padding
+            }
+            (Layer::Layer3, _) => 72 * bitrate / sample_rate + padding,
+        };
+        Some(length)
+    }
+
+    /// Parse the header at the start of `header` into frame info, without touching any decoder state.
+    ///
+    /// This gives the same info as [get_next_frame_info](Self::get_next_frame_info) for Layer 3 frames, but
+    /// doesn't need a decoder. `size` is 0 for free format frames. Returns None if the header is invalid
+    pub fn parse_frame_info(header: &[u8]) -> Option<MP3FrameInfo> {
+        let (version, layer, bitrate, sample_rate, _) = Self::header_rates(header)?;
+        let channels = if *header.get(3)? >> 6 == 0b11 { 1 } else { 2 };
+        let mut frame =
+            MP3FrameInfo::with_fields(version, layer, bitrate as u32, sample_rate as u32, channels);
+        frame.size = Self::frame_length(header).unwrap_or(0) as i32;
+        Some(frame)
+    }
+
+    /// Parse the channel mode from the frame header at the start of `header`, without touching any decoder
+    /// state. Returns None if the header is invalid
+    pub fn parse_channel_mode(header: &[u8]) -> Option<ChannelMode> {
+        Self::header_rates(header)?;
+        let mode = match *header.get(3)? >> 6 {
+            0b00 => ChannelMode::Stereo,
+            0b01 => ChannelMode::JointStereo,
+            0b10 => ChannelMode::DualChannel,
+            _ => ChannelMode::Mono,
+        };
+        Some(mode)
+    }
+
+    /// Parse the raw 2-bit mode extension (0-3) from the frame header at the start of `header`, without
+    /// touching any decoder state. See [mode_ext](Self::mode_ext). Returns None if the header is invalid
+    pub fn parse_mode_ext(header: &[u8]) -> Option<u8> {
+        Self::header_rates(header)?;
+        Some((*header.get(3)? >> 4) & 0b11)
+    }
+
+    /// Find the start of the frame that contains `byte_offset`, so that data can be cut on a frame boundary.
+    ///
+    /// Frames are found by walking the frame headers from the start of `data`.
+    /// Returns None if there are no frames before `byte_offset`
+    pub fn frame_boundary_before(data: &[u8], byte_offset: usize) -> Option<usize> {
+        FrameWalker::new(data)
+            .map(|(start, _)| start)
+            .take_while(|start| *start <= byte_offset)
+            .last()
+    }
+
+    /// Find the start of the first frame at or after `byte_offset`, so that data can be cut on a frame boundary.
+    ///
+    /// Frames are found by walking the frame headers from the start of `data`.
+    /// Returns None if there are no frames after `byte_offset`
+    pub fn frame_boundary_at_or_after(data: &[u8], byte_offset: usize) -> Option<usize> {
+        FrameWalker::new(data)
+            .map(|(start, _)| start)
+            .find(|start| *start >= byte_offset)
+    }
+
+    /// Iterate over the frames of `mp3buf` without decoding them, eg to build a seek index for a VBR stream
+    /// without a Xing TOC. See [FrameScanIter]
+    pub fn scan_frames(mp3buf: &[u8]) -> FrameScanIter<'_> {
+        FrameScanIter {
+            frames: FrameWalker::new(mp3buf),
+        }
+    }
+
+    /// Length of the Layer 3 side info of the frame whose header is at the start of `frame`
+    fn side_info_len(frame: &[u8]) -> Option<usize> {
+        let (version, _) = Self::parse_version_layer(frame)?;
+        let mono = *frame.get(3)? >> 6 == 0b11;
+        let side_info = match (version, mono) {
+            (MpegVersion::Mpeg1, false) => SIBYTES_MPEG1_STEREO,
+            (MpegVersion::Mpeg1, true) => SIBYTES_MPEG1_MONO,
+            (_, false) => SIBYTES_MPEG2_STEREO,
+            (_, true) => SIBYTES_MPEG2_MONO,
+        } as usize;
+        Some(side_info)
+    }
+
+    /// Whether the header at the start of `frame` is followed by a 16 bit CRC. The protection bit is 0 when it is
+    fn has_crc(frame: &[u8]) -> bool {
+        matches!(frame.get(1), Some(b1) if b1 & 1 == 0)
+    }
+
+    /// How many bytes of main data from earlier frames the frame at the start of `frame` uses from the bit reservoir.
+    /// This is the main_data_begin field at the start of the side info
+    fn parse_main_data_begin(frame: &[u8]) -> Option<usize> {
+        let (version, _) = Self::parse_version_layer(frame)?;
+        let crc = if Self::has_crc(frame) { 2 } else { 0 };
+        let side_info = frame.get(4 + crc..6 + crc)?;
+        let begin = match version {
+            MpegVersion::Mpeg1 => (side_info[0] as usize) << 1 | (side_info[1] >> 7) as usize,
+            _ => side_info[0] as usize,
+        };
+        Some(begin)
+    }
+
+    /// Whether the bit reservoir data used by the frame at the start of `frame` is available to the decoder.
+    ///
+    /// This is false when decoding starts part way through a stream (eg joining an internet radio stream)
+    /// and the frame uses data from earlier frames that were never decoded, so it would decode to garbage.
+    /// Returns None if `frame` doesn't start with a frame header and the start of its side info
+    pub fn has_main_data(&self, frame: &[u8]) -> Option<bool> {
+        let begin = Self::parse_main_data_begin(frame)?;
+        Some(begin <= self.mp3_dec_info.mainDataBytes.max(0) as usize)
+    }
+
+    /// Offset of the Xing/Info tag in the first frame of a stream; it follows the header, CRC and side info
+    fn xing_offset(frame: &[u8]) -> Option<usize> {
+        let side_info = Self::side_info_len(frame)?;
+        let crc = if Self::has_crc(frame) { 2 } else { 0 };
+        Some(4 + crc + side_info)
+    }
+
+    /// Check the CRC of the Layer 3 frame at the start of `frame`.
+    ///
+    /// The CRC-16 (polynomial 0x8005, initial value 0xFFFF, ISO 11172-3 2.4.3.1) covers the last 2 bytes
+    /// of the header and the side info, and is stored between them.
+    /// Returns None if the frame isn't protected by a CRC, is too short to check, or isn't Layer 3, as
+    /// Layer I and II CRCs cover different fields
+    pub fn verify_frame_crc(frame: &[u8]) -> Option<bool> {
+        let (_, layer) = Self::parse_version_layer(frame)?;
+        if !Self::has_crc(frame) || layer != Layer::Layer3 {
+            return None;
+        }
+        let side_info = frame.get(6..6 + Self::side_info_len(frame)?)?;
+        let mut crc: u16 = 0xFFFF;
+        for byte in frame[2..4].iter().chain(side_info) {
+            crc ^= (*byte as u16) << 8;
+            for _ in 0..8 {
+                crc = if crc & 0x8000 != 0 {
+                    (crc << 1) ^ 0x8005
+                } else {
+                    crc << 1
+                };
+            }
+        }
+        Some(crc == u16::from_be_bytes([frame[4], frame[5]]))
+    }
+
+    /// Check the frame at the start of `data` for deviations from the MPEG audio spec that decoders normally tolerate:
+    /// - a reserved bitrate, sample rate or emphasis value in the header
+    /// - a CRC that doesn't match the header and side info
+    /// - a frame length that doesn't lead to another frame header (or ID3v1 tag). This is only checked if `data`
+    ///   extends past the end of the frame, and not for free format frames
+    pub fn check_conformance(data: &[u8]) -> Result<(), SpecViolation> {
+        let [_, _, b2, b3, ..] = data else {
+            return Ok(());
+        };
+        if b2 >> 4 == 0b1111 || (b2 >> 2) & 0b11 == 0b11 {
+            return Err(SpecViolation::ReservedIndex);
+        }
+        if b3 & 0b11 == 0b10 {
+            return Err(SpecViolation::ReservedEmphasis);
+        }
+        if Self::verify_frame_crc(data) == Some(false) {
+            return Err(SpecViolation::CrcMismatch);
+        }
+        if let Some(next) = Self::frame_length(data).and_then(|len| data.get(len..len + 3)) {
+            if Self::parse_version_layer(next).is_none() && next != b"TAG" {
+                return Err(SpecViolation::FrameSizeMismatch);
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the Xing/Info tag in the first frame of a stream.
+    /// Returns its flags and the offset of the first field after them
+    fn find_xing(frame: &[u8]) -> Option<(u32, usize)> {
+        let offset = Self::xing_offset(frame)?;
+        let tag = frame.get(offset..offset + 8)?;
+        if &tag[0..4] != b"Xing" && &tag[0..4] != b"Info" {
+            return None;
+        }
+        let flags = u32::from_be_bytes([tag[4], tag[5], tag[6], tag[7]]);
+        Some((flags, offset + 8))
+    }
+
+    /// Read the Xing/Info header from the first frame of a stream. Returns None if there is no Xing/Info header.
+    ///
+    /// With `frames` and the frame info of any frame you can compute the exact duration of a VBR stream as
+    /// `frames * samples per frame / samprate`, which CBR bitrate maths gets wrong
+    pub fn parse_vbr_header(first_frame: &[u8]) -> Option<VbrInfo> {
+        let (flags, mut offset) = Self::find_xing(first_frame)?;
+        let mut info = VbrInfo {
+            frames: None,
+            bytes: None,
+            toc: None,
+            quality: None,
+            vbr: &first_frame[offset - 8..offset - 4] == b"Xing",
+            encoder_delay: 0,
+            encoder_padding: 0,
+        };
+        let read_u32 = |offset: &mut usize| {
+            let field = first_frame.get(*offset..*offset + 4)?;
+            *offset += 4;
+            Some(u32::from_be_bytes([field[0], field[1], field[2], field[3]]))
+        };
+        if flags & 0x1 != 0 {
+            info.frames = Some(read_u32(&mut offset)?);
+        }
+        if flags & 0x2 != 0 {
+            info.bytes = Some(read_u32(&mut offset)?);
+        }
+        if flags & 0x4 != 0 {
+            let mut toc = [0; 100];
+            toc.copy_from_slice(first_frame.get(offset..offset + 100)?);
+            info.toc = Some(toc);
+            offset += 100;
+        }
+        if flags & 0x8 != 0 {
+            info.quality = Some(read_u32(&mut offset)?);
+        }
+        // the encoder delay and padding are 12 bits each
+        if let Some(lame) = Self::find_lame(first_frame) {
+            let [d0, d1, d2] = [lame[21], lame[22], lame[23]].map(u16::from);
+            info.encoder_delay = d0 << 4 | d1 >> 4;
+            info.encoder_padding = (d1 & 0xF) << 8 | d2;
+        }
+        Some(info)
+    }
+
+    /// Work out whether the stream starting at the frame at the start of `data` is constant or variable bitrate,
+    /// by comparing the bitrates in up to `max_frames` frame headers. Nothing is decoded.
+    ///
+    /// A Xing or Info header in the first frame answers the question straight away.
+    /// Otherwise at least 2 frames are needed, and a stream that only changes bitrate after `max_frames`
+    /// frames is reported as constant
+    pub fn detect_bitrate_mode(data: &[u8], max_frames: usize) -> BitrateMode {
+        let bitrate = |offset: usize| Self::parse_frame_info(&data[offset..]).map(|f| f.bitrate);
+        if let (Some(vbr), Some(first)) = (Self::parse_vbr_header(data), bitrate(0)) {
+            return if vbr.vbr {
+                BitrateMode::Variable
+            } else {
+                BitrateMode::Constant(first as u32)
+            };
+        }
+        let mut frames = FrameWalker::new(data)
+            .take(max_frames)
+            .filter_map(|(offset, _)| bitrate(offset));
+        let Some(first) = frames.next() else {
+            return BitrateMode::Unknown;
+        };
+        let mut count = 1;
+        for bitrate in frames {
+            if bitrate != first {
+                return BitrateMode::Variable;
+            }
+            count += 1;
+        }
+        match count {
+            1 => BitrateMode::Unknown,
+            _ => BitrateMode::Constant(first as u32),
+        }
+    }
+
+    /// Estimate how long a whole MP3 file plays for, in seconds. Returns None if no valid frame is found.
+    ///
+    /// If the first frame has a Xing/Info header with a frame count the duration is exact. Otherwise it is
+    /// worked out from the bitrate of the first frame and the size of the file, excluding ID3 tags,
+    /// which is only accurate for CBR streams
+    pub fn estimate_duration_secs(mp3buf: &[u8]) -> Option<f32> {
+        let audio_start = match Self::find_id3v2(mp3buf) {
+            Some((offset, id3)) => offset + id3.tag_len(),
+            None => 0,
+        };
+        let audio_end = match Self::find_id3v1(mp3buf) {
+            Some(_) => mp3buf.len() - 128,
+            None => mp3buf.len(),
+        };
+        let mut audio = mp3buf.get(audio_start..audio_end)?;
+        // leave out an APE tag before or after the audio
+        if let Some((offset, ape)) = Self::find_apev2(audio) {
+            if offset == 0 {
+                audio = audio.get(ape.size..)?;
+            } else if offset.saturating_add(ape.size) == audio.len() {
+                audio = &audio[..offset];
+            }
+        }
+        let (offset, _) = FrameWalker::new(audio).next()?;
+        let first_frame = &audio[offset..];
+        let frame = Self::parse_frame_info(first_frame)?;
+        if let Some(frames) = Self::parse_vbr_header(first_frame).and_then(|vbr| vbr.frames) {
+            let samples = (frame.outputSamps / frame.nChans) as f32;
+            return Some(frames as f32 * samples / frame.samprate as f32);
+        }
+        Some((audio.len() - offset) as f32 * 8.0 / frame.bitrate as f32)
+    }
+
+    /// Find the LAME extension that follows the Xing/Info tag in the first frame of a stream
+    fn find_lame(frame: &[u8]) -> Option<&[u8]> {
+        let (flags, mut offset) = Self::find_xing(frame)?;
+        // skip over the optional frame count, byte count, TOC and quality fields
+        for (flag, len) in [(0x1, 4), (0x2, 4), (0x4, 100), (0x8, 4)] {
+            if flags & flag != 0 {
+                offset += len;
+            }
+        }
+        let lame = frame.get(offset..offset + 36)?;
+        if lame.starts_with(b"LAME") || lame.starts_with(b"Lav") {
+            Some(lame)
+        } else {
+            None
+        }
+    }
+
+    // from http://gabriel.mp3-tech.org/mp3infotag.html
+    // Each ReplayGain field is 16 bits: NNNOOOSV VVVVVVVV
+    // N is the name code (1 = track, 2 = album), O is the originator, S is the sign, V is the gain in 0.1dB
+    //
+    /// Read the ReplayGain adjustments from the LAME tag of the first frame of a stream.
+    /// Returns None if there is no LAME tag
+    pub fn parse_replaygain(first_frame: &[u8]) -> Option<ReplayGain> {
+        let lame = Self::find_lame(first_frame)?;
+        let mut gain = ReplayGain::default();
+        for field in [&lame[15..17], &lame[17..19]] {
+            let raw = u16::from_be_bytes([field[0], field[1]]);
+            let value = (raw & 0x1FF) as i16;
+            let value = if raw & 0x200 != 0 { -value } else { value };
+            match raw >> 13 {
+                1 => gain.track = Some(value),
+                2 => gain.album = Some(value),
+                _ => {}
+            }
+        }
+        Some(gain)
+    }
+
     // from https://mutagen-specs.readthedocs.io/en/latest/id3/id3v2.4.0-structure.html
     // ID3 tag format is as follows
     // $49 44 33 yy yy xx zz zz zz zz
@@ -287,10 +1271,12 @@ impl Mp3 {
         let window = mp3buf.windows(10);
         for (offset, slice) in window.enumerate() {
             if let [b'I', b'D', b'3', major, minor, flags, s1, s2, s3, s4] = slice {
+                // yy yy is the major version and revision, eg 04 00 for ID3v2.4.0. Neither can be $FF
                 let version = match (major, minor) {
-                    (2, 2) => Id3v2Version::ID3v2_2,
-                    (2, 3) => Id3v2Version::ID3v2_3,
-                    (2, 4) => Id3v2Version::ID3v2_4,
+                    (_, 0xFF) => Id3v2Version::Invalid,
+                    (2, _) => Id3v2Version::ID3v2_2,
+                    (3, _) => Id3v2Version::ID3v2_3,
+                    (4, _) => Id3v2Version::ID3v2_4,
                     (_, _) => Id3v2Version::Invalid,
                 };
                 let id3v2_flags = Id3v2Flags {
@@ -304,7 +1290,7 @@ impl Mp3 {
                 // The ID3v2 tag size is stored as a 32 bit synchsafe integer, making a total of 28 effective bits (representing up to 256MB).
                 // a syncsafe integer is a 7bit integer where the top bit is always zero.
                 let valid_syncsafe = (s1 | s2 | s3 | s4) & 0b1000_0000 != 0b1000_0000;
-                if version == Id3v2Version::Invalid && valid_syncsafe && valid_flags {
+                if version != Id3v2Version::Invalid && valid_syncsafe && valid_flags {
                     let (s1, s2, s3, s4) = (*s1 as usize, *s2 as usize, *s3 as usize, *s4 as usize);
                     let size = s4 | s3 << 7 | s2 << 14 | s1 << 21;
                     return Some((
@@ -321,6 +1307,235 @@ impl Mp3 {
         None
     }
 
+    /// Whether `mp3buf` could be the start of an ID3v2 header that has been cut off,
+    /// so more data is needed to rule out a tag at the start of the stream
+    pub(crate) fn partial_id3v2_at_start(mp3buf: &[u8]) -> bool {
+        let len = mp3buf.len().min(3);
+        mp3buf.len() < 10 && mp3buf[..len] == b"ID3"[..len]
+    }
+
+    // from https://wiki.hydrogenaud.io/index.php?title=APEv2_specification
+    // APE tag headers and footers are 32 bytes, all numbers are little endian
+    // "APETAGEX", version(4), tag size(4), item count(4), flags(4), reserved(8)
+    // The tag size includes the items and footer but not the header. Flag bit 31 is set when the tag
+    // has a header, and bit 29 is set in the header but not the footer
+    //
+    /// Find and decode an APE tag header or footer, which can be placed before or after the audio.
+    /// Returns the offset in the provided slice where the tag starts and the decoded tag info, or None
+    pub fn find_apev2(mp3buf: &[u8]) -> Option<(usize, ApeTag)> {
+        let window = mp3buf.windows(32);
+        for (offset, slice) in window.enumerate() {
+            if !slice.starts_with(b"APETAGEX") {
+                continue;
+            }
+            let le32 = |at: usize| {
+                u32::from_le_bytes([slice[at], slice[at + 1], slice[at + 2], slice[at + 3]])
+            };
+            let (version, tag_size, items, flags) = (le32(8), le32(12), le32(16), le32(20));
+            let has_header = flags & (1 << 31) != 0;
+            let is_header = flags & (1 << 29) != 0;
+            let size = (tag_size as usize).saturating_add(if has_header { 32 } else { 0 });
+            // the footer is at the end of the tag, so the tag starts before it
+            let start = if is_header {
+                Some(offset)
+            } else {
+                (offset + 32).checked_sub(size)
+            };
+            if let Some(start) = start {
+                return Some((
+                    start,
+                    ApeTag {
+                        version,
+                        size,
+                        items,
+                        has_header,
+                    },
+                ));
+            }
+        }
+        None
+    }
+
+    // from https://id3.org/ID3v1
+    // ID3v1 tag format is as follows, in the last 128 bytes of the stream
+    // "TAG", title(30), artist(30), album(30), year(4), comment(30), genre(1)
+    // ID3v1.1 stores the track number in the last byte of the comment, preceded by a zero byte
+    //
+    /// Find and decode an ID3v1 tag in the last 128 bytes of the provided slice, or None
+    pub fn find_id3v1(mp3buf: &[u8]) -> Option<Id3v1<'_>> {
+        let tag = mp3buf.get(mp3buf.len().checked_sub(128)?..)?;
+        if &tag[0..3] != b"TAG" {
+            return None;
+        }
+        fn trim(field: &[u8]) -> &[u8] {
+            let field = match field.iter().position(|b| *b == 0) {
+                Some(end) => &field[..end],
+                None => field,
+            };
+            let len = field.len() - field.iter().rev().take_while(|b| **b == b' ').count();
+            &field[..len]
+        }
+        let comment = &tag[97..127];
+        let track = match comment[28..30] {
+            [0, track] if track != 0 => Some(track),
+            _ => None,
+        };
+        Some(Id3v1 {
+            title: trim(&tag[3..33]),
+            artist: trim(&tag[33..63]),
+            album: trim(&tag[63..93]),
+            year: trim(&tag[93..97]),
+            comment: trim(comment),
+            track,
+            genre: tag[127],
+        })
+    }
+
+    /// Raw 2-bit mode extension (0-3) of the most recently parsed frame header.
+    ///
+    /// This is only meaningful for joint stereo frames. For Layer 3, bit 0 indicates intensity stereo
+    /// and bit 1 indicates M/S stereo are in use.
+    /// The mode extension is not part of [MP3FrameInfo], which is ThreePM's C struct, so this reads
+    /// the decoder's frame header. Use [parse_mode_ext](Self::parse_mode_ext) to read it from the
+    /// header of a particular frame instead
+    pub fn mode_ext(&self) -> u8 {
+        self.mp3_dec_info.fh.modeExt as u8
+    }
+
+    /// The most recently parsed frame header, with its bitrate, sample rate, channel mode and emphasis decoded.
+    /// Returns None if no frame header has been parsed yet
+    pub fn frame_header(&self) -> Option<DecodedHeader> {
+        let fh = &self.mp3_dec_info.fh;
+        let version = match fh.ver {
+            crate::ffi::MPEGVersion_MPEG1 => MpegVersion::Mpeg1,
+            crate::ffi::MPEGVersion_MPEG2 => MpegVersion::Mpeg2,
+            crate::ffi::MPEGVersion_MPEG25 => MpegVersion::Mpeg25,
+            _ => return None,
+        };
+        let layer = match fh.layer {
+            1 => Layer::Layer1,
+            2 => Layer::Layer2,
+            3 => Layer::Layer3,
+            _ => return None,
+        };
+        let (bitrate_kbps, sample_rate) =
+            Self::lookup_rates(version, layer, fh.brIdx as usize, fh.srIdx as usize)?;
+        let emphasis = match fh.emphasis {
+            0 => Emphasis::None,
+            1 => Emphasis::FiftyFifteen,
+            3 => Emphasis::CcittJ17,
+            _ => Emphasis::Reserved,
+        };
+        Some(DecodedHeader {
+            version,
+            layer,
+            bitrate_kbps,
+            sample_rate,
+            channel_mode: self.channel_mode(),
+            mode_extension: self.mode_ext(),
+            emphasis,
+            crc: fh.crc != 0,
+            padding: fh.paddingBit != 0,
+            private: fh.privateBit != 0,
+            copyright: fh.copyFlag != 0,
+            original: fh.origFlag != 0,
+        })
+    }
+
+    /// Channel mode of the most recently parsed frame header.
+    ///
+    /// [MP3FrameInfo] is ThreePM's C struct and only has the channel count, not the header's stereo
+    /// mode, so this can't be a method on it. Use [parse_channel_mode](Self::parse_channel_mode) to
+    /// read the mode of a particular frame from its header instead
+    pub fn channel_mode(&self) -> ChannelMode {
+        match self.mp3_dec_info.fh.sMode {
+            crate::ffi::StereoMode_Joint => ChannelMode::JointStereo,
+            crate::ffi::StereoMode_Dual => ChannelMode::DualChannel,
+            crate::ffi::StereoMode_Mono => ChannelMode::Mono,
+            _ => ChannelMode::Stereo,
+        }
+    }
+
+    /// Block type (0-3) used for a granule and channel of the most recently decoded frame, or None if the
+    /// frame doesn't have that granule or channel.
+    ///
+    /// 0 is a normal long block, 1 is a start block, 2 is short blocks and 3 is a stop block.
+    /// Encoders switch to short blocks to avoid pre-echo around transients
+    pub fn block_type(&self, granule: usize, channel: usize) -> Option<u8> {
+        let info = &self.mp3_dec_info;
+        if granule >= info.nGrans.max(0) as usize || channel >= info.nChans.max(0) as usize {
+            return None;
+        }
+        let sis = info.si.sis.get(granule)?.get(channel)?;
+        Some(sis.blockType as u8)
+    }
+
+    /// How many bytes back into the bit reservoir the main data of the most recently decoded frame started,
+    /// ie its main_data_begin back pointer. 0 means the frame didn't use the bit reservoir
+    pub fn main_data_begin(&self) -> usize {
+        self.mp3_dec_info.mainDataBegin.max(0) as usize
+    }
+
+    /// Length in bits of the scale factors and Huffman coded data (part2_3_length) of each granule and channel
+    /// of the most recently decoded frame, indexed as `[granule][channel]`.
+    /// Entries for granules or channels the frame doesn't have are 0
+    pub fn part23_length(&self) -> [[usize; 2]; 2] {
+        let info = &self.mp3_dec_info;
+        let mut lengths = [[0; 2]; 2];
+        let (grans, chans) = (info.nGrans.max(0) as usize, info.nChans.max(0) as usize);
+        for (granule, lengths) in lengths.iter_mut().enumerate().take(grans) {
+            for (channel, length) in lengths.iter_mut().enumerate().take(chans) {
+                *length = info.part23Length[granule][channel].max(0) as usize;
+            }
+        }
+        lengths
+    }
+
+    /// Whether the decoder has seen a free format frame, which has no bitrate in its header.
+    /// The decoder has to find the next sync word to work out how long these frames are, which fails with
+    /// [DecodeErr::FreeBitrateSync] if it can't
+    pub fn is_free_bitrate(&self) -> bool {
+        self.mp3_dec_info.freeBitrateFlag != 0
+    }
+
+    /// Number of main data bytes (slots) in each frame of the free format stream being decoded, as worked out
+    /// by the decoder from the distance to the next sync word. 0 if it isn't known yet
+    pub fn free_bitrate_slots(&self) -> usize {
+        self.mp3_dec_info.freeBitrateSlots.max(0) as usize
+    }
+
+    /// Rough level of each of the 32 subbands in the last granule of the most recently decoded frame,
+    /// as the sum of the absolute IMDCT output values across all channels. Index 0 is the lowest frequency band
+    pub fn subband_levels(&self) -> [u64; 32] {
+        let info = &self.mp3_dec_info;
+        let mut levels = [0u64; 32];
+        for channel in info.mi.outBuf.iter().take(info.nChans.max(0) as usize) {
+            for block in channel {
+                for (level, sample) in levels.iter_mut().zip(block) {
+                    *level += sample.unsigned_abs() as u64;
+                }
+            }
+        }
+        levels
+    }
+
+    /// Search ID3v2 tag data for a numeric TXXX (user defined text) frame with the description `key`,
+    /// eg the `LOOPSTART` tag used to mark loop points in game music. Only Latin-1 and UTF-8 frames are found
+    pub fn find_txxx_number(tag: &[u8], key: &[u8]) -> Option<u64> {
+        let needle_len = key.len() + 1;
+        let start = tag
+            .windows(needle_len)
+            .position(|w| &w[..key.len()] == key && w[key.len()] == 0)?;
+        let value = &tag[start + needle_len..];
+        let digits = value.iter().take_while(|b| b.is_ascii_digit()).count();
+        if digits == 0 {
+            return None;
+        }
+        value[..digits]
+            .iter()
+            .try_fold(0u64, |acc, d| acc.checked_mul(10)?.checked_add((d - b'0') as u64))
+    }
+
     /// Expose underlying C void pointer HMP3Decoder. For when you need to use ffi functions that aren't wrapped
     ///
     /// # Safety
@@ -336,3 +1551,826 @@ impl Default for Mp3 {
         Self::new()
     }
 }
+
+/// A lightweight cursor over the frames of an in-memory MP3 stream, for navigating and indexing it
+/// without a decoder.
+///
+/// Frames are found by parsing each header and jumping ahead by the frame length, scanning ahead for
+/// the next header where there isn't a valid one. Nothing is decoded, and free format frames are skipped
+pub struct FrameCursor<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> FrameCursor<'a> {
+    /// Construct a cursor at the start of `data`
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    /// Byte offset of the cursor in the stream
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Parse the info of the next frame and move the cursor past it. Returns None at the end of the stream
+    pub fn next_frame_info(&mut self) -> Option<MP3FrameInfo> {
+        let mut frames = FrameWalker {
+            data: self.data,
+            offset: self.offset,
+        };
+        let Some((start, length)) = frames.next() else {
+            self.offset = self.data.len();
+            return None;
+        };
+        self.offset = start + length;
+        Mp3::parse_frame_info(&self.data[start..])
+    }
+
+    /// Move the cursor to the start of frame `n` (counting from 0) of the stream.
+    /// Returns the byte offset of the frame, or None without moving the cursor if there aren't that many frames
+    pub fn seek_to_frame(&mut self, n: usize) -> Option<usize> {
+        let (start, _) = FrameWalker::new(self.data).nth(n)?;
+        self.offset = start;
+        Some(start)
+    }
+}
+
+/// A frame found by [Mp3::scan_frames]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameEntry {
+    /// Offset of the frame header in the scanned data
+    pub byte_offset: usize,
+    /// Number of samples per channel the frame decodes to
+    pub samples: u32,
+    /// Length of the frame in bytes
+    pub size: usize,
+}
+
+/// Iterator over the frames of an in-memory MP3 stream, returned by [Mp3::scan_frames].
+///
+/// Frames are found by parsing each header and jumping ahead by the frame length, scanning ahead for
+/// the next header where there isn't a valid one. Nothing is decoded, and free format frames are skipped.
+/// Summing `samples` over the frames before a frame gives its position in the stream
+pub struct FrameScanIter<'a> {
+    frames: FrameWalker<'a>,
+}
+
+impl Iterator for FrameScanIter<'_> {
+    type Item = FrameEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (byte_offset, size) = self.frames.next()?;
+        let frame = Mp3::parse_frame_info(&self.frames.data[byte_offset..])?;
+        Some(FrameEntry {
+            byte_offset,
+            samples: (frame.outputSamps / frame.nChans.max(1)) as u32,
+            size,
+        })
+    }
+}
+
+/// Walks the frames in a buffer by parsing each header and jumping ahead by the frame length,
+/// without decoding anything. Where there isn't a valid header it scans ahead for the next one.
+/// Yields the offset and length of each frame
+struct FrameWalker<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> FrameWalker<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+}
+
+impl Iterator for FrameWalker<'_> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.offset + 4 <= self.data.len() {
+            if let Some(length) = Mp3::frame_length(&self.data[self.offset..]) {
+                let start = self.offset;
+                self.offset += length;
+                return Some((start, length));
+            }
+            self.offset += 1;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_state() {
+        let mut mp3 = Mp3::new();
+        mp3.mp3_dec_info.mi.overBuf[0][5] = 42;
+        mp3.mp3_dec_info.sbi.vindex = 3;
+        let snapshot = mp3.clone();
+        mp3.reset();
+        assert_eq!(mp3.mp3_dec_info.mi.overBuf[0][5], 0);
+        mp3.clone_from(&snapshot);
+        assert_eq!(mp3.mp3_dec_info.mi.overBuf[0][5], 42);
+        assert_eq!(mp3.mp3_dec_info.sbi.vindex, 3);
+    }
+
+    #[test]
+    fn version_layer_mpeg1_layer3() {
+        let header = [0xFF, 0xFB, 0x90, 0x64];
+        assert_eq!(
+            Mp3::parse_version_layer(&header),
+            Some((MpegVersion::Mpeg1, Layer::Layer3))
+        );
+    }
+
+    #[test]
+    fn version_layer_mpeg2_layer2() {
+        let header = [0xFF, 0xF4, 0x90, 0x64];
+        assert_eq!(
+            Mp3::parse_version_layer(&header),
+            Some((MpegVersion::Mpeg2, Layer::Layer2))
+        );
+    }
+
+    /// Build an MPEG-1 Layer 3 stereo Info frame with a LAME tag holding the provided ReplayGain fields
+    fn lame_frame(track: u16, album: u16) -> [u8; 417] {
+        let mut frame = [0u8; 417];
+        frame[0..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        // Info tag with only the frame count field present
+        frame[36..44].copy_from_slice(b"Info\0\0\0\x01");
+        frame[48..57].copy_from_slice(b"LAME3.100");
+        frame[63..65].copy_from_slice(&track.to_be_bytes());
+        frame[65..67].copy_from_slice(&album.to_be_bytes());
+        frame
+    }
+
+    #[test]
+    fn replaygain_from_lame_tag() {
+        // track gain of -6.5dB, album gain of +1.2dB, both set by the user (originator 3)
+        let frame = lame_frame(0b0010_1110_0100_0001, 0b0100_1100_0000_1100);
+        let gain = Mp3::parse_replaygain(&frame).unwrap();
+        assert_eq!(gain.track, Some(-65));
+        assert_eq!(gain.album, Some(12));
+    }
+
+    #[test]
+    fn vbr_header_from_xing_tag() {
+        let mut frame = lame_frame(0, 0);
+        // Xing tag with frame count, byte count and quality fields
+        frame[36..44].copy_from_slice(b"Xing\0\0\0\x0B");
+        frame[44..48].copy_from_slice(&1000u32.to_be_bytes());
+        frame[48..52].copy_from_slice(&417_000u32.to_be_bytes());
+        frame[52..56].copy_from_slice(&57u32.to_be_bytes());
+        let info = Mp3::parse_vbr_header(&frame).unwrap();
+        assert_eq!(info.frames, Some(1000));
+        assert_eq!(info.bytes, Some(417_000));
+        assert_eq!(info.toc, None);
+        assert_eq!(info.quality, Some(57));
+        assert!(info.vbr);
+        assert!(Mp3::parse_vbr_header(&[0xFF, 0xFB, 0x90, 0x64]).is_none());
+    }
+
+    #[test]
+    fn lame_encoder_delay_and_padding() {
+        let mut frame = lame_frame(0, 0);
+        let info = Mp3::parse_vbr_header(&frame).unwrap();
+        assert_eq!((info.encoder_delay, info.encoder_padding), (0, 0));
+        // 576 samples delay, 1234 samples padding
+        frame[69..72].copy_from_slice(&[0x24, 0x04, 0xD2]);
+        let info = Mp3::parse_vbr_header(&frame).unwrap();
+        assert!(!info.vbr);
+        assert_eq!(info.frames, Some(0));
+        assert_eq!(info.encoder_delay, 576);
+        assert_eq!(info.encoder_padding, 1234);
+        // both fields are 12 bits
+        frame[69..72].copy_from_slice(&[0xFF, 0xFF, 0xFF]);
+        let info = Mp3::parse_vbr_header(&frame).unwrap();
+        assert_eq!((info.encoder_delay, info.encoder_padding), (4095, 4095));
+        // an Info tag without a LAME extension has no delay or padding
+        frame[48..72].fill(0);
+        let info = Mp3::parse_vbr_header(&frame).unwrap();
+        assert_eq!((info.encoder_delay, info.encoder_padding), (0, 0));
+    }
+
+    #[test]
+    fn vbr_toc_seek() {
+        let mut toc = [0u8; 100];
+        for (i, entry) in toc.iter_mut().enumerate() {
+            *entry = (i * 256 / 100) as u8;
+        }
+        let mut info = VbrInfo {
+            frames: Some(1000),
+            bytes: Some(1000),
+            toc: Some(toc),
+            quality: None,
+            vbr: true,
+            encoder_delay: 0,
+            encoder_padding: 0,
+        };
+        assert_eq!(info.seek_byte_for_percent(0.0), Some(0));
+        assert_eq!(info.seek_byte_for_percent(50.0), Some(500));
+        // halfway between toc[25] = 64 and toc[26] = 66
+        assert_eq!(info.seek_byte_for_percent(25.5), Some(253));
+        assert_eq!(info.seek_byte_for_percent(100.0), Some(1000));
+        assert_eq!(info.seek_byte_for_percent(150.0), Some(1000));
+        assert_eq!(info.seek_byte_in(50.0, 2000), Some(1000));
+        info.bytes = None;
+        assert_eq!(info.seek_byte_for_percent(50.0), None);
+        info.toc = None;
+        assert_eq!(info.seek_byte_in(50.0, 2000), None);
+    }
+
+    #[test]
+    fn replaygain_not_set() {
+        let frame = lame_frame(0, 0);
+        assert_eq!(Mp3::parse_replaygain(&frame), Some(ReplayGain::default()));
+        assert_eq!(Mp3::parse_replaygain(&frame[0..40]), None);
+    }
+
+    /// Build a buffer of `N` bytes of junk followed by frames of MPEG-1 Layer 3, 128kbps, 44.1kHz audio
+    fn frames<const N: usize>() -> [u8; N] {
+        let mut data = [0u8; N];
+        let mut offset = 3;
+        while offset + 4 <= N {
+            data[offset..offset + 4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+            offset += 417;
+        }
+        data
+    }
+
+    #[test]
+    fn frame_lengths() {
+        // MPEG-1 Layer 3, 128kbps, 44.1kHz, unpadded and padded
+        assert_eq!(Mp3::frame_length(&[0xFF, 0xFB, 0x90, 0x64]), Some(417));
+        assert_eq!(Mp3::frame_length(&[0xFF, 0xFB, 0x92, 0x64]), Some(418));
+        // MPEG-2 Layer 3, 64kbps, 22.05kHz
+        assert_eq!(Mp3::frame_length(&[0xFF, 0xF3, 0x80, 0xC4]), Some(208));
+        // free format and invalid bitrates
+        assert_eq!(Mp3::frame_length(&[0xFF, 0xFB, 0x00, 0x64]), None);
+        assert_eq!(Mp3::frame_length(&[0xFF, 0xFB, 0xF0, 0x64]), None);
+        // reserved sample rate
+        assert_eq!(Mp3::frame_length(&[0xFF, 0xFB, 0x9C, 0x64]), None);
+    }
+
+    #[test]
+    fn frame_size_from_header() {
+        // MPEG-1 Layer 3, 128kbps, 44.1kHz, unpadded and padded
+        assert_eq!(Mp3::frame_size_bytes(&[0xFF, 0xFB, 0x90, 0x64]), Some(417));
+        assert_eq!(Mp3::frame_size_bytes(&[0xFF, 0xFB, 0x92, 0x64]), Some(418));
+        // MPEG-2 Layer 3, 64kbps, 22.05kHz, unpadded and padded
+        assert_eq!(Mp3::frame_size_bytes(&[0xFF, 0xF3, 0x80, 0xC4]), Some(208));
+        assert_eq!(Mp3::frame_size_bytes(&[0xFF, 0xF3, 0x82, 0xC4]), Some(209));
+        // free format, invalid bitrate and no sync word
+        assert_eq!(Mp3::frame_size_bytes(&[0xFF, 0xFB, 0x00, 0x64]), None);
+        assert_eq!(Mp3::frame_size_bytes(&[0xFF, 0xFB, 0xF0, 0x64]), None);
+        assert_eq!(Mp3::frame_size_bytes(&[0x49, 0x44, 0x33, 0x04]), None);
+    }
+
+    #[test]
+    fn frame_info_from_header() {
+        let frame = Mp3::parse_frame_info(&[0xFF, 0xFB, 0x90, 0x64]).unwrap();
+        assert_eq!(frame.bitrate, 128000);
+        assert_eq!(frame.nChans, 2);
+        assert_eq!(frame.samprate, 44100);
+        assert_eq!(frame.outputSamps, 2304);
+        assert_eq!(frame.layer, 3);
+        assert_eq!(frame.version, 0);
+        assert_eq!(frame.size, 417);
+        // MPEG-2 Layer 3, 64kbps, 22.05kHz, mono
+        let frame = Mp3::parse_frame_info(&[0xFF, 0xF3, 0x80, 0xC0]).unwrap();
+        assert_eq!(frame.nChans, 1);
+        assert_eq!(frame.samprate, 22050);
+        assert_eq!(frame.outputSamps, 576);
+        assert_eq!(frame.version, 1);
+        assert_eq!(frame.size, 208);
+        assert!(Mp3::parse_frame_info(&[0xFF, 0xFB, 0x90]).is_none());
+    }
+
+    #[test]
+    fn bitrate_modes() {
+        let mut data = frames::<1200>();
+        assert_eq!(Mp3::detect_bitrate_mode(&data, 10), BitrateMode::Constant(128000));
+        assert_eq!(Mp3::detect_bitrate_mode(&data, 1), BitrateMode::Unknown);
+        // 160kbps frame
+        data[839] = 0xA0;
+        assert_eq!(Mp3::detect_bitrate_mode(&data, 10), BitrateMode::Variable);
+        assert_eq!(Mp3::detect_bitrate_mode(&data, 2), BitrateMode::Constant(128000));
+        let info = lame_frame(0, 0);
+        assert_eq!(Mp3::detect_bitrate_mode(&info, 10), BitrateMode::Constant(128000));
+        assert_eq!(Mp3::detect_bitrate_mode(&[0; 8], 10), BitrateMode::Unknown);
+    }
+
+    #[test]
+    fn frame_cursor() {
+        let data = frames::<1200>();
+        let mut cursor = FrameCursor::new(&data);
+        assert_eq!(cursor.next_frame_info().unwrap().size, 417);
+        assert_eq!(cursor.offset(), 420);
+        assert_eq!(cursor.seek_to_frame(2), Some(837));
+        assert_eq!(cursor.seek_to_frame(3), None);
+        assert_eq!(cursor.offset(), 837);
+        assert!(cursor.next_frame_info().is_some());
+        assert!(cursor.next_frame_info().is_none());
+        assert_eq!(cursor.seek_to_frame(0), Some(3));
+    }
+
+    #[test]
+    fn scan_frame_entries() {
+        let mut data = [0u8; 1045];
+        data[3..7].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        // MPEG-2 Layer 3, 64kbps, 22.05kHz, mono
+        data[420..424].copy_from_slice(&[0xFF, 0xF3, 0x80, 0xC4]);
+        data[628..632].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        let mut entries = Mp3::scan_frames(&data);
+        let entry = entries.next().unwrap();
+        assert_eq!((entry.byte_offset, entry.samples, entry.size), (3, 1152, 417));
+        let entry = entries.next().unwrap();
+        assert_eq!((entry.byte_offset, entry.samples, entry.size), (420, 576, 208));
+        let entry = entries.next().unwrap();
+        assert_eq!((entry.byte_offset, entry.samples, entry.size), (628, 1152, 417));
+        assert_eq!(entries.next(), None);
+        assert_eq!(Mp3::scan_frames(&[0; 100]).next(), None);
+    }
+
+    #[test]
+    fn duration_estimates() {
+        let mut data = frames::<1254>();
+        // 3 frames at 128kbps
+        let duration = Mp3::estimate_duration_secs(&data).unwrap();
+        assert!((duration - 0.07819).abs() < 0.0001);
+        // Info tag with a frame count of 100
+        data[39..47].copy_from_slice(b"Info\0\0\0\x01");
+        data[47..51].copy_from_slice(&100u32.to_be_bytes());
+        let duration = Mp3::estimate_duration_secs(&data).unwrap();
+        assert!((duration - 2.6122).abs() < 0.0001);
+        assert!(Mp3::estimate_duration_secs(&[0; 100]).is_none());
+    }
+
+    #[test]
+    fn mpeg25_frame_info() {
+        // MPEG-2.5 Layer 3, 8kbps, 8kHz, mono
+        let frame = Mp3::parse_frame_info(&[0xFF, 0xE3, 0x18, 0xC0]).unwrap();
+        assert_eq!(frame.mpeg_version(), MpegVersion::Mpeg25);
+        assert_eq!(frame.version, 2);
+        assert_eq!(frame.samprate, 8000);
+        assert_eq!(frame.outputSamps, 576);
+        assert_eq!(frame.size, 72);
+        assert_eq!(frame.frame_duration_us(), 72000);
+        // 11.025kHz stereo
+        let frame = Mp3::parse_frame_info(&[0xFF, 0xE3, 0x10, 0x00]).unwrap();
+        assert_eq!(frame.samprate, 11025);
+        assert_eq!(frame.outputSamps, 1152);
+    }
+
+    #[test]
+    fn main_data_availability() {
+        let mut mp3 = Mp3::new();
+        // MPEG-1 Layer 3 without CRC, main_data_begin is the first 9 bits of the side info
+        let mut frame = [0xFF, 0xFB, 0x90, 0x00, 0x00, 0x00];
+        assert_eq!(mp3.has_main_data(&frame), Some(true));
+        frame[4..6].copy_from_slice(&[0x02, 0x80]);
+        assert_eq!(Mp3::parse_main_data_begin(&frame), Some(5));
+        assert_eq!(mp3.has_main_data(&frame), Some(false));
+        mp3.mp3_dec_info.mainDataBytes = 5;
+        assert_eq!(mp3.has_main_data(&frame), Some(true));
+        // with a CRC the side info starts 2 bytes later
+        let crc_frame = [0xFF, 0xFA, 0x90, 0x00, 0x12, 0x34, 0x04, 0x00];
+        assert_eq!(Mp3::parse_main_data_begin(&crc_frame), Some(8));
+        assert_eq!(mp3.has_main_data(&frame[..5]), None);
+    }
+
+    #[test]
+    fn decoded_frame_header() {
+        let mut mp3 = Mp3::new();
+        assert_eq!(mp3.frame_header(), None);
+        // MPEG-1 Layer 3, 128kbps, 44.1kHz, joint stereo with M/S, original
+        let fh = &mut mp3.mp3_dec_info.fh;
+        fh.layer = 3;
+        fh.brIdx = 9;
+        fh.sMode = crate::ffi::StereoMode_Joint;
+        fh.modeExt = 2;
+        fh.origFlag = 1;
+        let header = mp3.frame_header().unwrap();
+        assert_eq!(header.version, MpegVersion::Mpeg1);
+        assert_eq!(header.layer, Layer::Layer3);
+        assert_eq!(header.bitrate_kbps, 128);
+        assert_eq!(header.sample_rate, 44100);
+        assert_eq!(header.channel_mode, ChannelMode::JointStereo);
+        assert_eq!(header.mode_extension, 2);
+        assert_eq!(header.emphasis, Emphasis::None);
+        assert!(header.original && !header.copyright && !header.crc && !header.padding);
+        // MPEG-2 Layer 3, 64kbps, 16kHz, mono, CRC, copyright, 50/15us emphasis
+        let fh = &mut mp3.mp3_dec_info.fh;
+        fh.ver = crate::ffi::MPEGVersion_MPEG2;
+        fh.brIdx = 8;
+        fh.srIdx = 2;
+        fh.sMode = crate::ffi::StereoMode_Mono;
+        fh.crc = 1;
+        fh.copyFlag = 1;
+        fh.emphasis = 1;
+        let header = mp3.frame_header().unwrap();
+        assert_eq!(header.bitrate_kbps, 64);
+        assert_eq!(header.sample_rate, 16000);
+        assert_eq!(header.channel_mode, ChannelMode::Mono);
+        assert_eq!(header.emphasis, Emphasis::FiftyFifteen);
+        assert!(header.crc && header.copyright);
+        // MPEG-2.5 Layer 1, 256kbps, 11.025kHz
+        let fh = &mut mp3.mp3_dec_info.fh;
+        fh.ver = crate::ffi::MPEGVersion_MPEG25;
+        fh.layer = 1;
+        fh.brIdx = 14;
+        fh.srIdx = 0;
+        let header = mp3.frame_header().unwrap();
+        assert_eq!(header.bitrate_kbps, 256);
+        assert_eq!(header.sample_rate, 11025);
+        // index 15 is invalid
+        mp3.mp3_dec_info.fh.brIdx = 15;
+        assert_eq!(mp3.frame_header(), None);
+    }
+
+    #[test]
+    fn bit_reservoir_info() {
+        let mut mp3 = Mp3::new();
+        assert_eq!(mp3.main_data_begin(), 0);
+        assert_eq!(mp3.part23_length(), [[0; 2]; 2]);
+        let info = &mut mp3.mp3_dec_info;
+        info.mainDataBegin = 300;
+        info.nGrans = 1;
+        info.nChans = 2;
+        info.part23Length = [[1000, 1200], [900, 800]];
+        assert_eq!(mp3.main_data_begin(), 300);
+        // only the first granule is used
+        assert_eq!(mp3.part23_length(), [[1000, 1200], [0, 0]]);
+    }
+
+    #[test]
+    fn free_bitrate_info() {
+        let mut mp3 = Mp3::new();
+        assert!(!mp3.is_free_bitrate());
+        assert_eq!(mp3.free_bitrate_slots(), 0);
+        mp3.mp3_dec_info.freeBitrateFlag = 1;
+        mp3.mp3_dec_info.freeBitrateSlots = 600;
+        assert!(mp3.is_free_bitrate());
+        assert_eq!(mp3.free_bitrate_slots(), 600);
+        mp3.reset();
+        assert!(!mp3.is_free_bitrate());
+    }
+
+    #[test]
+    fn frame_info_with_fields() {
+        let frame = MP3FrameInfo::with_fields(MpegVersion::Mpeg1, Layer::Layer3, 128_000, 44100, 2);
+        assert_eq!(Some(frame), Mp3::parse_frame_info(&[0xFF, 0xFB, 0x90, 0x00]));
+        assert_eq!(frame.outputSamps, 2304);
+        assert_eq!(frame.size, 417);
+        let frame = MP3FrameInfo::with_fields(MpegVersion::Mpeg25, Layer::Layer3, 8000, 8000, 1);
+        assert_eq!(frame.outputSamps, 576);
+        assert_eq!(frame.size, 72);
+        let stereo = MP3FrameInfo::with_fields(MpegVersion::Mpeg25, Layer::Layer3, 8000, 8000, 2);
+        assert_ne!(frame, stereo);
+    }
+
+    #[test]
+    fn typed_frame_info() {
+        let frame = Mp3::parse_frame_info(&[0xFF, 0xF3, 0x80, 0xC0]).unwrap();
+        assert_eq!(frame.mpeg_layer(), Layer::Layer3);
+        assert_eq!(frame.mpeg_version(), MpegVersion::Mpeg2);
+        let frame = MP3FrameInfo::with_fields(MpegVersion::Mpeg1, Layer::Layer2, 128_000, 44100, 2);
+        assert_eq!(frame.mpeg_layer(), Layer::Layer2);
+        assert_eq!(frame.mpeg_version(), MpegVersion::Mpeg1);
+        let empty = MP3FrameInfo::new();
+        assert_eq!(empty.mpeg_layer(), Layer::Layer3);
+        assert_eq!(empty.mpeg_version(), MpegVersion::Mpeg1);
+    }
+
+    #[test]
+    fn mode_ext_from_header() {
+        // joint stereo with M/S but not intensity stereo
+        assert_eq!(Mp3::parse_mode_ext(&[0xFF, 0xFB, 0x90, 0x64]), Some(2));
+        assert_eq!(Mp3::parse_mode_ext(&[0xFF, 0xFB, 0x90, 0x74]), Some(3));
+        assert_eq!(Mp3::parse_mode_ext(&[0xFF, 0xFB, 0x90, 0xC4]), Some(0));
+        assert_eq!(Mp3::parse_mode_ext(&[0xFF, 0xFB, 0x90]), None);
+    }
+
+    #[test]
+    fn channel_mode_from_header() {
+        let mode = |b3| Mp3::parse_channel_mode(&[0xFF, 0xFB, 0x90, b3]);
+        assert_eq!(mode(0x04), Some(ChannelMode::Stereo));
+        assert_eq!(mode(0x64), Some(ChannelMode::JointStereo));
+        assert_eq!(mode(0x84), Some(ChannelMode::DualChannel));
+        assert_eq!(mode(0xC4), Some(ChannelMode::Mono));
+        assert_eq!(Mp3::parse_channel_mode(&[0xFF, 0xFB, 0xF0, 0x64]), None);
+    }
+
+    #[test]
+    fn valid_frame_headers() {
+        assert!(Mp3::is_valid_frame_header(&[0xFF, 0xFB, 0x90, 0x64]));
+        // free format
+        assert!(Mp3::is_valid_frame_header(&[0xFF, 0xFB, 0x00, 0x64]));
+        // bad bitrate index, bad sample rate index, reserved version, reserved layer
+        assert!(!Mp3::is_valid_frame_header(&[0xFF, 0xFB, 0xF0, 0x64]));
+        assert!(!Mp3::is_valid_frame_header(&[0xFF, 0xFB, 0x9C, 0x64]));
+        assert!(!Mp3::is_valid_frame_header(&[0xFF, 0xEB, 0x90, 0x64]));
+        assert!(!Mp3::is_valid_frame_header(&[0xFF, 0xF9, 0x90, 0x64]));
+    }
+
+    #[test]
+    fn sync_word_offset() {
+        let data = frames::<1200>();
+        assert_eq!(Mp3::find_sync_word_offset(&data), Some(3));
+        assert_eq!(Mp3::find_sync_word_offset(&[0; 16]), None);
+    }
+
+    #[test]
+    fn frame_boundaries() {
+        let data = frames::<1200>();
+        assert_eq!(Mp3::frame_boundary_before(&data, 0), None);
+        assert_eq!(Mp3::frame_boundary_before(&data, 3), Some(3));
+        assert_eq!(Mp3::frame_boundary_before(&data, 500), Some(420));
+        assert_eq!(Mp3::frame_boundary_at_or_after(&data, 0), Some(3));
+        assert_eq!(Mp3::frame_boundary_at_or_after(&data, 420), Some(420));
+        assert_eq!(Mp3::frame_boundary_at_or_after(&data, 500), Some(837));
+        assert_eq!(Mp3::frame_boundary_at_or_after(&data, 1000), None);
+    }
+
+    #[test]
+    fn frame_duration_and_size() {
+        let frame = MP3FrameInfo {
+            bitrate: 128000,
+            nChans: 2,
+            samprate: 44100,
+            bitsPerSample: 16,
+            outputSamps: 2304,
+            layer: 3,
+            version: 0,
+            size: 417,
+        };
+        assert_eq!(frame.frame_duration_us(), 26122);
+        assert_eq!(frame.bytes_per_frame(), 4608);
+        let empty = MP3FrameInfo::new();
+        assert_eq!(empty.frame_duration_us(), 0);
+        assert_eq!(empty.bytes_per_frame(), 0);
+    }
+
+    #[test]
+    fn find_id3v2_header() {
+        let mut data = [0u8; 32];
+        data[2..12].copy_from_slice(&[b'I', b'D', b'3', 4, 0, 0b0100_0000, 0, 0, 0x02, 0x01]);
+        let (offset, tag) = Mp3::find_id3v2(&data).unwrap();
+        assert_eq!(offset, 2);
+        assert_eq!(tag.version, Id3v2Version::ID3v2_4);
+        assert!(tag.flags.extended_header);
+        assert!(!tag.flags.footer_present);
+        assert_eq!(tag.size, 257);
+        assert_eq!(tag.tag_len(), 267);
+        // size is not a valid syncsafe integer
+        data[11] = 0x81;
+        assert!(Mp3::find_id3v2(&data).is_none());
+    }
+
+    #[test]
+    fn id3v2_extended_header_len() {
+        // ID3v2.4 tag with a 6 byte extended header, a 4 byte frame and a footer, then audio
+        let mut data = [0u8; 48];
+        data[..10].copy_from_slice(&[b'I', b'D', b'3', 4, 0, 0b0101_0000, 0, 0, 0, 10]);
+        // extended header: syncsafe size (including itself), flag byte count, flags
+        data[10..16].copy_from_slice(&[0, 0, 0, 6, 1, 0]);
+        data[16..20].copy_from_slice(b"TXXX");
+        data[20..30].copy_from_slice(&[b'3', b'D', b'I', 4, 0, 0b0101_0000, 0, 0, 0, 10]);
+        data[30..32].copy_from_slice(&[0xFF, 0xFB]);
+        let (offset, tag) = Mp3::find_id3v2(&data).unwrap();
+        assert_eq!(offset, 0);
+        assert!(tag.flags.extended_header);
+        // the extended header is part of the tag size, so the skip lands on the audio
+        assert_eq!(tag.tag_len(), 30);
+        assert_eq!(&data[tag.tag_len()..tag.tag_len() + 2], &[0xFF, 0xFB]);
+    }
+
+    #[test]
+    fn id3v2_footer_len() {
+        let mut data = [0u8; 16];
+        data[..10].copy_from_slice(&[b'I', b'D', b'3', 4, 0, 0b0001_0000, 0, 0, 0x01, 0x00]);
+        let (_, tag) = Mp3::find_id3v2(&data).unwrap();
+        assert!(tag.flags.footer_present);
+        assert_eq!(tag.tag_len(), 10 + 128 + 10);
+        // ID3v2.3 has no footer
+        data[3] = 3;
+        let (_, tag) = Mp3::find_id3v2(&data).unwrap();
+        assert_eq!(tag.tag_len(), 10 + 128);
+    }
+
+    #[test]
+    fn apev2_size_saturates() {
+        let mut data = [0u8; 32];
+        data[..8].copy_from_slice(b"APETAGEX");
+        data[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        data[20..24].copy_from_slice(&(1u32 << 31 | 1 << 29).to_le_bytes());
+        let (offset, ape) = Mp3::find_apev2(&data).unwrap();
+        assert_eq!(offset, 0);
+        assert!(ape.size >= u32::MAX as usize);
+        assert!(Mp3::estimate_duration_secs(&data).is_none());
+    }
+
+    #[test]
+    fn partial_id3v2_header() {
+        assert!(Mp3::partial_id3v2_at_start(b"I"));
+        assert!(Mp3::partial_id3v2_at_start(b"ID3\x04\x00"));
+        assert!(!Mp3::partial_id3v2_at_start(b"ID4\x04\x00"));
+        // only a tag at the start is waited for, not the end of the audio
+        assert!(!Mp3::partial_id3v2_at_start(b"\xFF\xFBID3\x04\x00"));
+        assert!(!Mp3::partial_id3v2_at_start(b"\xFF\xFB\x90\x64\x00I"));
+        // a complete header can't have been cut off
+        assert!(!Mp3::partial_id3v2_at_start(b"ID3\x04\x00\x00\x00\x00\x00\x00"));
+    }
+
+    #[test]
+    fn id3v2_text_frames() {
+        let mut tag = [0u8; 64];
+        // ID3v2.4 tag with a 6 byte extended header, then two text frames and padding
+        tag[..10].copy_from_slice(&[b'I', b'D', b'3', 4, 0, 0b0100_0000, 0, 0, 0x01, 0x10]);
+        tag[10..16].copy_from_slice(&[0, 0, 0, 6, 1, 0]);
+        tag[16..26].copy_from_slice(&[b'T', b'I', b'T', b'2', 0, 0, 0, 4, 0, 0]);
+        tag[26..30].copy_from_slice(b"\x03abc");
+        tag[30..40].copy_from_slice(&[b'T', b'P', b'E', b'1', 0, 0, 0, 2, 0, 0]);
+        tag[40..42].copy_from_slice(b"\x03x");
+        let (_, id3) = Mp3::find_id3v2(&tag).unwrap();
+        let mut frames = id3.frames(&tag);
+        assert_eq!(frames.next(), Some((*b"TIT2", &b"\x03abc"[..])));
+        assert_eq!(frames.next(), Some((*b"TPE1", &b"\x03x"[..])));
+        // padding
+        assert_eq!(frames.next(), None);
+
+        // ID3v2.3 sizes aren't syncsafe
+        let mut tag = [0u8; 150];
+        tag[..10].copy_from_slice(&[b'I', b'D', b'3', 3, 0, 0, 0, 0, 0x01, 0x0A]);
+        tag[10..20].copy_from_slice(&[b'T', b'A', b'L', b'B', 0, 0, 0, 0x80, 0, 0]);
+        let (_, id3) = Mp3::find_id3v2(&tag).unwrap();
+        let (id, data) = id3.frames(&tag).next().unwrap();
+        assert_eq!((id, data.len()), (*b"TALB", 128));
+
+        // ID3v2.2 has 3 byte IDs and sizes
+        let mut tag = [0u8; 32];
+        tag[..10].copy_from_slice(&[b'I', b'D', b'3', 2, 0, 0, 0, 0, 0, 20]);
+        tag[10..19].copy_from_slice(&[b'T', b'T', b'2', 0, 0, 3, 0, b'h', b'i']);
+        let (_, id3) = Mp3::find_id3v2(&tag).unwrap();
+        let mut frames = id3.frames(&tag);
+        assert_eq!(frames.next(), Some((*b"TT2\0", &b"\0hi"[..])));
+        assert_eq!(frames.next(), None);
+    }
+
+    #[test]
+    fn find_id3v1_tag() {
+        let mut data = [0u8; 200];
+        let tag = &mut data[72..];
+        tag[0..3].copy_from_slice(b"TAG");
+        tag[3..13].copy_from_slice(b"Title     ");
+        tag[33..39].copy_from_slice(b"Artist");
+        tag[93..97].copy_from_slice(b"1999");
+        tag[97..104].copy_from_slice(b"Comment");
+        tag[126] = 7;
+        tag[127] = 17;
+        let id3 = Mp3::find_id3v1(&data).unwrap();
+        assert_eq!(id3.title, b"Title");
+        assert_eq!(id3.artist, b"Artist");
+        assert_eq!(id3.album, b"");
+        assert_eq!(id3.year, b"1999");
+        assert_eq!(id3.comment, b"Comment");
+        assert_eq!(id3.track, Some(7));
+        assert_eq!(id3.genre, 17);
+        // tag must be at the very end of the data
+        assert!(Mp3::find_id3v1(&data[..199]).is_none());
+        assert!(Mp3::find_id3v1(&data[..100]).is_none());
+    }
+
+    /// Write an APE header or footer into the start of `out`
+    fn ape_header(out: &mut [u8], tag_size: u32, flags: u32) {
+        out[0..8].copy_from_slice(b"APETAGEX");
+        out[8..12].copy_from_slice(&2000u32.to_le_bytes());
+        out[12..16].copy_from_slice(&tag_size.to_le_bytes());
+        out[16..20].copy_from_slice(&3u32.to_le_bytes());
+        out[20..24].copy_from_slice(&flags.to_le_bytes());
+    }
+
+    #[test]
+    fn find_apev2_tag() {
+        // tag with a header and footer, 16 bytes of items
+        let mut data = [0u8; 200];
+        ape_header(&mut data[20..], 48, 1 << 31 | 1 << 29);
+        ape_header(&mut data[68..], 48, 1 << 31);
+        let (offset, ape) = Mp3::find_apev2(&data).unwrap();
+        assert_eq!(offset, 20);
+        assert_eq!(ape.size, 80);
+        assert_eq!(ape.version, 2000);
+        assert_eq!(ape.items, 3);
+        assert!(ape.has_header);
+        // the header is missing, so the footer says the tag starts before the data does
+        assert!(Mp3::find_apev2(&data[52..]).is_none());
+        // footer only, found from the end of the tag
+        let mut data = [0u8; 100];
+        ape_header(&mut data[68..], 48, 0);
+        let (offset, ape) = Mp3::find_apev2(&data).unwrap();
+        assert_eq!(offset, 52);
+        assert_eq!(ape.size, 48);
+        assert!(!ape.has_header);
+    }
+
+    #[test]
+    fn duration_without_apev2() {
+        let mut data = [0u8; 1254 + 64];
+        data[..1254].copy_from_slice(&frames::<1254>());
+        ape_header(&mut data[1286..], 64, 0);
+        let duration = Mp3::estimate_duration_secs(&data).unwrap();
+        assert!((duration - 0.07819).abs() < 0.0001);
+    }
+
+    #[test]
+    fn txxx_numbers() {
+        let tag = b"TXXX\0\0\0\x12\0\0\x03LOOPSTART\x00441000TXXX\0\0\0\x0F\0\0\x03LOOPLENGTH\x001234\0";
+        assert_eq!(Mp3::find_txxx_number(tag, b"LOOPSTART"), Some(441000));
+        assert_eq!(Mp3::find_txxx_number(tag, b"LOOPLENGTH"), Some(1234));
+        assert_eq!(Mp3::find_txxx_number(tag, b"LOOPEND"), None);
+        assert_eq!(Mp3::find_txxx_number(b"LOOPSTART\0abc", b"LOOPSTART"), None);
+    }
+
+    #[test]
+    fn conformance_checks() {
+        let data = frames::<1200>();
+        assert_eq!(Mp3::check_conformance(&data[420..]), Ok(()));
+        let mut bad = data;
+        // the next frame doesn't start where this one ends
+        bad[837] = 0;
+        assert_eq!(
+            Mp3::check_conformance(&bad[420..]),
+            Err(SpecViolation::FrameSizeMismatch)
+        );
+        bad[423] |= 0b10;
+        assert_eq!(
+            Mp3::check_conformance(&bad[420..]),
+            Err(SpecViolation::ReservedEmphasis)
+        );
+        bad[422] |= 0b1111_0000;
+        assert_eq!(
+            Mp3::check_conformance(&bad[420..]),
+            Err(SpecViolation::ReservedIndex)
+        );
+    }
+
+    #[test]
+    fn frame_crc_verification() {
+        let mut frame = [0u8; 40];
+        // MPEG-1 Layer 3 mono with CRC, side info of 17 zero bytes
+        frame[0..4].copy_from_slice(&[0xFF, 0xFA, 0x90, 0xC0]);
+        frame[4..6].copy_from_slice(&[0xD0, 0xBF]);
+        assert_eq!(Mp3::verify_frame_crc(&frame), Some(true));
+        frame[10] = 1;
+        assert_eq!(Mp3::verify_frame_crc(&frame), Some(false));
+        assert_eq!(Mp3::verify_frame_crc(&frame[..20]), None);
+        frame[1] = 0xFB;
+        assert_eq!(Mp3::verify_frame_crc(&frame), None);
+    }
+
+    #[test]
+    fn frame_crc_stereo_bit_flip() {
+        let mut frame = [0u8; 417];
+        // MPEG-1 Layer 3 joint stereo with CRC, 32 bytes of side info
+        frame[0..4].copy_from_slice(&[0xFF, 0xFA, 0x90, 0x44]);
+        frame[4..6].copy_from_slice(&[0x5B, 0x78]);
+        for (i, byte) in frame[6..38].iter_mut().enumerate() {
+            *byte = (i as u8).wrapping_mul(37).wrapping_add(11);
+        }
+        assert_eq!(Mp3::verify_frame_crc(&frame), Some(true));
+        // main data isn't covered by the CRC
+        frame[200] ^= 0xFF;
+        assert_eq!(Mp3::verify_frame_crc(&frame), Some(true));
+        // a single flipped bit in the side info or the header is caught
+        frame[37] ^= 0x01;
+        assert_eq!(Mp3::verify_frame_crc(&frame), Some(false));
+        frame[37] ^= 0x01;
+        frame[3] ^= 0x04;
+        assert_eq!(Mp3::verify_frame_crc(&frame), Some(false));
+    }
+
+    #[test]
+    fn frame_crc_not_checked_for_layer_1_and_2() {
+        let mut frame = [0u8; 417];
+        // MPEG-1 Layer 2 and Layer 1 with CRC, whose CRCs cover more than the Layer 3 side info
+        for header in [[0xFF, 0xFC, 0x90, 0x44], [0xFF, 0xFE, 0x90, 0x44]] {
+            frame[0..4].copy_from_slice(&header);
+            assert_eq!(Mp3::verify_frame_crc(&frame), None);
+            assert_eq!(Mp3::check_conformance(&frame[..40]), Ok(()));
+        }
+    }
+
+    #[test]
+    fn version_layer_rejects_reserved() {
+        // reserved version
+        assert_eq!(Mp3::parse_version_layer(&[0xFF, 0xEB]), None);
+        // reserved layer
+        assert_eq!(Mp3::parse_version_layer(&[0xFF, 0xF9]), None);
+        // no sync word
+        assert_eq!(Mp3::parse_version_layer(&[0x49, 0x44, 0x33]), None);
+        assert_eq!(Mp3::parse_version_layer(&[0xFF]), None);
+    }
+}
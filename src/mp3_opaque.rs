@@ -0,0 +1,78 @@
+//! Wrapper for `ThreePM` where the C library owns the decoder state, for use with the `use-static-buffers` feature.
+//!
+//! [crate::mp3::Mp3] holds all of the decoder state (~24KB) in the struct itself. This [Mp3] only holds
+//! a handle to the state, which the C library keeps in its own static buffers instead.
+use crate::ffi::HMP3Decoder;
+use crate::mp3::{DecodeErr, MP3FrameInfo};
+
+/// MP3 decoding context object, using the decoder state allocated by `ThreePM`
+///
+/// The C library only has one set of static buffers, so every `Mp3` shares the same decoder state.
+/// Only create one at a time, and don't mix it with [crate::mp3::Mp3] decoders on the same stream.
+#[derive(Debug)]
+pub struct Mp3 {
+    decoder: HMP3Decoder,
+}
+
+impl Mp3 {
+    /// Get a handle to the decoder state from `ThreePM`. Returns None if the C library couldn't provide one
+    pub fn new() -> Option<Self> {
+        let decoder = unsafe { crate::ffi::MP3InitDecoder() };
+        if decoder.is_null() {
+            None
+        } else {
+            Some(Self { decoder })
+        }
+    }
+
+    /// Get info for the most recently decoded MP3 frame
+    pub fn get_last_frame_info(&mut self) -> MP3FrameInfo {
+        let mut frame = MP3FrameInfo::new();
+        unsafe { crate::ffi::MP3GetLastFrameInfo(self.decoder, &mut frame) };
+        frame
+    }
+
+    /// Get info for the next MP3 frame
+    pub fn get_next_frame_info(&mut self, mp3buf: &[u8]) -> Result<MP3FrameInfo, DecodeErr> {
+        let mut frame = MP3FrameInfo::new();
+        let err =
+            unsafe { crate::ffi::MP3GetNextFrameInfo(self.decoder, &mut frame, mp3buf.as_ptr()) };
+        if err == 0 {
+            // No error, return the frame info
+            Ok(frame)
+        } else {
+            Err(err.into())
+        }
+    }
+
+    /// Decode the next MP3 frame
+    pub fn decode(
+        &mut self,
+        mp3buf: &[u8],
+        newlen: i32,
+        buf: &mut [i16],
+    ) -> Result<i32, DecodeErr> {
+        let mut newlen = newlen;
+        let err = unsafe {
+            crate::ffi::MP3Decode(
+                self.decoder,
+                &mut mp3buf.as_ptr(),
+                &mut newlen,
+                buf.as_mut_ptr(),
+                0,
+            )
+        };
+        if err == 0 {
+            // No error, return the new length of the source buffer
+            Ok(newlen)
+        } else {
+            Err(err.into())
+        }
+    }
+}
+
+impl Drop for Mp3 {
+    fn drop(&mut self) {
+        unsafe { crate::ffi::MP3FreeDecoder(self.decoder) };
+    }
+}
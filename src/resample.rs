@@ -0,0 +1,108 @@
+//! Linear interpolation sample rate conversion of decoded samples.
+
+/// Linear resampler state, carried between frames so that there is no step at frame boundaries
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Resampler {
+    /// Position of the next output sample in Q32 fixed point, in input samples per channel counted from
+    /// the last sample of the previous frame
+    position: u64,
+    /// The last sample of each channel of the previous frame
+    last: [i16; 2],
+    /// The (input rate, output rate, channels) the state is for, None before the first frame
+    format: Option<(u32, u32, usize)>,
+}
+
+impl Resampler {
+    pub(crate) const fn new() -> Self {
+        Resampler {
+            position: 0,
+            last: [0; 2],
+            format: None,
+        }
+    }
+
+    /// The most samples that resampling `input_len` interleaved samples can output
+    pub(crate) fn max_output_len(
+        input_len: usize,
+        channels: usize,
+        in_rate: u32,
+        out_rate: u32,
+    ) -> usize {
+        let channels = channels.clamp(1, 2);
+        let frames = (input_len / channels) as u64;
+        match (frames * out_rate as u64).checked_div(in_rate as u64) {
+            Some(out_frames) => (out_frames as usize + 2) * channels,
+            None => 0,
+        }
+    }
+
+    /// Resample interleaved `input` from `in_rate` to `out_rate` into `output`, which should hold
+    /// [max_output_len](Self::max_output_len) samples. Restarts on the first sample of `input` if the
+    /// format changed. Returns the number of samples written
+    pub(crate) fn process(
+        &mut self,
+        input: &[i16],
+        channels: usize,
+        in_rate: u32,
+        out_rate: u32,
+        output: &mut [i16],
+    ) -> usize {
+        let channels = channels.clamp(1, 2);
+        let frames = input.len() / channels;
+        if frames == 0 || in_rate == 0 || out_rate == 0 {
+            return 0;
+        }
+        if self.format != Some((in_rate, out_rate, channels)) {
+            self.format = Some((in_rate, out_rate, channels));
+            self.position = 1 << 32;
+        }
+        let step = ((in_rate as u64) << 32) / out_rate as u64;
+        let mut written = 0;
+        while self.position >> 32 < frames as u64 && written + channels <= output.len() {
+            let index = (self.position >> 32) as usize;
+            let frac = (self.position & 0xFFFF_FFFF) as i64;
+            for channel in 0..channels {
+                let before = match index {
+                    0 => self.last[channel],
+                    _ => input[(index - 1) * channels + channel],
+                } as i64;
+                let after = input[index * channels + channel] as i64;
+                output[written + channel] = (before + (((after - before) * frac) >> 32)) as i16;
+            }
+            written += channels;
+            self.position += step;
+        }
+        self.position = self.position.saturating_sub((frames as u64) << 32);
+        self.last[..channels].copy_from_slice(&input[(frames - 1) * channels..frames * channels]);
+        written
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upsample_across_frames() {
+        let mut resampler = Resampler::new();
+        let mut out = [0i16; 16];
+        let written = resampler.process(&[0, 100, 200, 300], 1, 22050, 44100, &mut out);
+        assert_eq!(out[..written], [0, 50, 100, 150, 200, 250]);
+        // carries on from the end of the previous frame without a step
+        let written = resampler.process(&[400, 500], 1, 22050, 44100, &mut out);
+        assert_eq!(out[..written], [300, 350, 400, 450]);
+        assert!(written <= Resampler::max_output_len(2, 1, 22050, 44100));
+    }
+
+    #[test]
+    fn downsample_stereo() {
+        let mut resampler = Resampler::new();
+        let mut input = [1000i16; 2304];
+        input.iter_mut().skip(1).step_by(2).for_each(|sample| *sample = -1000);
+        let mut out = [0i16; 2304];
+        let written = resampler.process(&input, 2, 48000, 44100, &mut out);
+        assert!(written <= Resampler::max_output_len(input.len(), 2, 48000, 44100));
+        assert_eq!(written, 1058 * 2);
+        assert!(out[..written].chunks(2).all(|pair| pair == [1000, -1000]));
+    }
+}
@@ -0,0 +1,122 @@
+//! An adapter for using `ThreePM` as the MP3 decoder of a [symphonia](https://docs.rs/symphonia) based player.
+use crate::easy_mode::{EasyModeErr, EasyModeN};
+use crate::mp3::MAX_OUTPUT_SAMPLES;
+use std::boxed::Box;
+use symphonia_core::audio::{
+    AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
+};
+use symphonia_core::codecs::{
+    CodecDescriptor, CodecParameters, Decoder, DecoderOptions, FinalizeResult, CODEC_TYPE_MP3,
+};
+use symphonia_core::errors::{decode_error, unsupported_error, Result};
+use symphonia_core::formats::Packet;
+
+/// Size of the MP3 stream buffer, which holds the packet being decoded and the bit reservoir it refers to
+const BUFFER_SIZE: usize = 4096;
+
+const CODECS: &[CodecDescriptor] = &[CodecDescriptor {
+    codec: CODEC_TYPE_MP3,
+    short_name: "mp3",
+    long_name: "MPEG Audio Layer 3 (ThreePM)",
+    inst_func: |params, options| Ok(Box::new(ThreePmDecoder::try_new(params, options)?)),
+}];
+
+/// A symphonia [Decoder] for MP3 packets, backed by [EasyModeN].
+///
+/// Register it with a `CodecRegistry` to use it in place of symphonia's own MP3 decoder.
+/// Each packet is added to the MP3 stream buffer and one frame is decoded from it. The sample rate
+/// and channels in [codec_params](Decoder::codec_params) are filled in from the first decoded frame
+pub struct ThreePmDecoder {
+    easy: EasyModeN<BUFFER_SIZE>,
+    params: CodecParameters,
+    frame: [i16; MAX_OUTPUT_SAMPLES],
+    buf: AudioBuffer<i16>,
+}
+
+impl Decoder for ThreePmDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        if params.codec != CODEC_TYPE_MP3 {
+            return unsupported_error("threepm: invalid codec type");
+        }
+        Ok(Self {
+            easy: EasyModeN::new(),
+            params: params.clone(),
+            frame: [0; MAX_OUTPUT_SAMPLES],
+            buf: AudioBuffer::unused(),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        CODECS
+    }
+
+    fn reset(&mut self) {
+        self.easy.reset();
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        let mut data = packet.buf();
+        while !data.is_empty() {
+            let added = self.easy.add_data(data);
+            if added == 0 {
+                return decode_error("threepm: packet is larger than the stream buffer");
+            }
+            data = &data[added..];
+        }
+        if !self.easy.mp3_decode_ready() {
+            return decode_error("threepm: no mp3 frame in packet");
+        }
+
+        let samples = match self.easy.decode(&mut self.frame) {
+            Ok(samples) => samples,
+            // there's not enough data for a frame yet, or its bit reservoir is missing (eg after a seek)
+            Err(EasyModeErr::InDataUnderflow | EasyModeErr::MaindataUnderfow) => {
+                self.buf.clear();
+                return Ok(self.buf.as_audio_buffer_ref());
+            }
+            Err(_) => return decode_error("threepm: failed to decode frame"),
+        };
+        let Ok(frame) = self.easy.mp3_info() else {
+            return decode_error("threepm: failed to decode frame");
+        };
+
+        let channels = if frame.nChans == 2 {
+            Channels::FRONT_LEFT | Channels::FRONT_RIGHT
+        } else {
+            Channels::FRONT_LEFT
+        };
+        if self.params.sample_rate.is_none() {
+            self.params.with_sample_rate(frame.samprate as u32);
+        }
+        if self.params.channels.is_none() {
+            self.params.with_channels(channels);
+        }
+
+        let spec = SignalSpec::new(frame.samprate as u32, channels);
+        if *self.buf.spec() != spec || self.buf.capacity() < MAX_OUTPUT_SAMPLES / 2 {
+            self.buf = AudioBuffer::new((MAX_OUTPUT_SAMPLES / 2) as u64, spec);
+        }
+        let count = channels.count();
+        self.buf.clear();
+        self.buf.render_reserved(Some(samples / count));
+        for ch in 0..count {
+            let decoded = self.frame[ch..samples].iter().step_by(count);
+            for (out, sample) in self.buf.chan_mut(ch).iter_mut().zip(decoded) {
+                *out = *sample;
+            }
+        }
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
@@ -0,0 +1,44 @@
+//! Regression test that the decoded output of the sample MP3 hasn't changed.
+//!
+//! Run it with `cargo test --features std -- --ignored` once `gs-16b-2c-44100hz.mp3` is in the crate root.
+#![cfg(feature = "std")]
+
+use threepm::easy_mode::EasyMode;
+
+const SAMPLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/gs-16b-2c-44100hz.mp3");
+
+/// FNV-1a hash of the decoded samples of the sample MP3. This has to be recorded from a known good build
+/// (the test prints it) and updated whenever a change to the decoded output is intended
+const EXPECTED_FNV1A: Option<u64> = None;
+
+/// 64 bit FNV-1a hash over the little endian bytes of `samples`
+fn fnv1a(samples: &[i16]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in samples.iter().flat_map(|sample| sample.to_le_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+#[test]
+fn fnv1a_reference_values() {
+    assert_eq!(fnv1a(&[]), 0xcbf2_9ce4_8422_2325);
+    // the bytes of "ab"
+    assert_eq!(fnv1a(&[i16::from_le_bytes(*b"ab")]), 0x089c_4407_b545_986a);
+}
+
+#[test]
+#[ignore = "needs gs-16b-2c-44100hz.mp3 in the crate root"]
+fn decoded_sample_fingerprint() {
+    let data = std::fs::read(SAMPLE).expect("sample MP3 is missing");
+    let mut easy = EasyMode::new();
+    let samples = easy.decode_all(&data).unwrap();
+    assert!(!samples.is_empty());
+    let hash = fnv1a(&samples);
+    println!("decoded {} samples with FNV-1a hash {hash:#018x}", samples.len());
+    match EXPECTED_FNV1A {
+        Some(expected) => assert_eq!(hash, expected, "decoded output has changed"),
+        None => panic!("no expected hash recorded yet, set EXPECTED_FNV1A to {hash:#018x}"),
+    }
+}